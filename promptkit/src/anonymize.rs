@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Serialize;
+
+/// Names that say nothing about a project and that code refers to (`fn main`, `mod.rs`),
+/// kept as they are.
+const CONVENTIONAL_NAMES: [&str; 40] = [
+    "src",
+    "lib",
+    "bin",
+    "app",
+    "cmd",
+    "pkg",
+    "internal",
+    "test",
+    "tests",
+    "spec",
+    "docs",
+    "doc",
+    "examples",
+    "benches",
+    "scripts",
+    "config",
+    "utils",
+    "util",
+    "main",
+    "mod",
+    "index",
+    "__init__",
+    "__main__",
+    "setup",
+    "build",
+    "README",
+    "LICENSE",
+    "CHANGELOG",
+    "Cargo",
+    "package",
+    "tsconfig",
+    "go",
+    "Makefile",
+    "Dockerfile",
+    "requirements",
+    "pyproject",
+    "public",
+    "assets",
+    "static",
+    "vendor",
+];
+
+static STRING_LITERAL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#""(?:[^"\\\n]|\\.)*""#).expect("invalid string literal pattern"));
+
+/// Words joined by `-` or `.`, which is how stems like `billing-api` appear in contents.
+static TOKEN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\w+(?:[-.]\w+)*").expect("invalid token pattern"));
+
+static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\w+").expect("invalid word pattern"));
+
+/// Consistent placeholders for the names in a dump: `dir_a/file_1.rs` for `billing/api.rs`.
+///
+/// Each path component is renamed by its stem, so `billing/` and `billing.rs` both become
+/// `dir_a`, and the same word in file contents (`mod billing;`) is renamed to match.
+#[derive(Default)]
+pub struct Anonymizer {
+    /// Original stem to placeholder stem.
+    stems: BTreeMap<String, String>,
+    /// Original string literal (with quotes) to placeholder, with `strings`.
+    literals: BTreeMap<String, String>,
+    strings: bool,
+    dirs: usize,
+    files: usize,
+}
+
+/// The mapping written next to an anonymized dump, placeholders first so it reads as a
+/// lookup table for the model's reply.
+#[derive(Serialize)]
+struct Mapping<'a> {
+    files: BTreeMap<String, &'a str>,
+    names: BTreeMap<&'a str, &'a str>,
+    strings: BTreeMap<&'a str, &'a str>,
+}
+
+impl Anonymizer {
+    /// Assigns placeholders to the components of `paths`; with `strings`, string literals in
+    /// contents are replaced too.
+    pub fn new<'a>(paths: impl IntoIterator<Item = &'a str>, strings: bool) -> Self {
+        let mut anonymizer = Anonymizer {
+            strings,
+            ..Default::default()
+        };
+        let mut paths: Vec<&str> = paths.into_iter().collect();
+        paths.sort();
+        // Directories first, so that `foo/` is a `dir_` even when `foo.rs` sorts before it.
+        for path in &paths {
+            if let Some((dirs, _)) = path.rsplit_once('/') {
+                for component in dirs.split('/') {
+                    anonymizer.assign(component, true);
+                }
+            }
+        }
+        for path in &paths {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            anonymizer.assign(name, false);
+        }
+        anonymizer
+    }
+
+    fn assign(&mut self, name: &str, is_dir: bool) {
+        let stem = stem(name);
+        if stem.is_empty()
+            || stem.starts_with('.')
+            || CONVENTIONAL_NAMES.contains(&stem)
+            || self.stems.contains_key(stem)
+        {
+            return;
+        }
+        let placeholder = if is_dir {
+            self.dirs += 1;
+            format!("dir_{}", letters(self.dirs))
+        } else {
+            self.files += 1;
+            format!("file_{}", self.files)
+        };
+        self.stems.insert(stem.to_string(), placeholder);
+    }
+
+    /// A relative path with each component renamed.
+    pub fn path(&self, path: &str) -> String {
+        path.split('/')
+            .map(|component| {
+                let stem = stem(component);
+                match self.stems.get(stem) {
+                    Some(placeholder) => format!("{placeholder}{}", &component[stem.len()..]),
+                    None => component.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// File contents with renamed names (as whole words) and, with `strings`, string literals.
+    pub fn contents(&mut self, contents: &str) -> String {
+        let mut text = if self.stems.is_empty() {
+            contents.to_string()
+        } else {
+            TOKEN
+                .replace_all(contents, |caps: &regex::Captures| self.token(&caps[0]))
+                .into_owned()
+        };
+        if self.strings {
+            text = STRING_LITERAL
+                .replace_all(&text, |caps: &regex::Captures| {
+                    let next = self.literals.len() + 1;
+                    self.literals
+                        .entry(caps[0].to_string())
+                        .or_insert_with(|| format!("\"str_{next}\""))
+                        .clone()
+                })
+                .into_owned();
+        }
+        text
+    }
+
+    /// `token` with each name in it renamed, trying the longest run of words first at each
+    /// word, so that `billing-api` is not renamed as `billing`.
+    fn token(&self, token: &str) -> String {
+        let words: Vec<(usize, usize)> = WORD
+            .find_iter(token)
+            .map(|word| (word.start(), word.end()))
+            .collect();
+        let mut out = String::new();
+        let mut copied = 0;
+        let mut idx = 0;
+        while idx < words.len() {
+            let start = words[idx].0;
+            let renamed = (idx + 1..=words.len()).rev().find_map(|end| {
+                let stem = &token[start..words[end - 1].1];
+                self.stems.get(stem).map(|placeholder| (end, placeholder))
+            });
+            match renamed {
+                Some((end, placeholder)) => {
+                    out.push_str(&token[copied..start]);
+                    out.push_str(placeholder);
+                    copied = words[end - 1].1;
+                    idx = end;
+                }
+                None => idx += 1,
+            }
+        }
+        out.push_str(&token[copied..]);
+        out
+    }
+
+    /// Writes the placeholder-to-original mapping of `files` and all names to `path`.
+    pub fn write_map<'a>(
+        &'a self,
+        path: &Path,
+        files: impl IntoIterator<Item = &'a str>,
+    ) -> Result<()> {
+        let mapping = Mapping {
+            files: files
+                .into_iter()
+                .map(|file| (self.path(file), file))
+                .collect(),
+            names: self
+                .stems
+                .iter()
+                .map(|(name, placeholder)| (placeholder.as_str(), name.as_str()))
+                .collect(),
+            strings: self
+                .literals
+                .iter()
+                .map(|(literal, placeholder)| (placeholder.as_str(), literal.as_str()))
+                .collect(),
+        };
+        let json = serde_json::to_string_pretty(&mapping)?;
+        fs::write(path, json + "\n").with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+/// The part of a file name before its last extension (`api` for `api.rs`).
+fn stem(name: &str) -> &str {
+    match name.rfind('.') {
+        Some(0) | None => name,
+        Some(dot) => &name[..dot],
+    }
+}
+
+/// `a`, `b`, ..., `z`, `aa`, `ab`, ... for 1, 2, ...
+fn letters(mut n: usize) -> String {
+    let mut out = Vec::new();
+    while n > 0 {
+        n -= 1;
+        out.push(b'a' + (n % 26) as u8);
+        n /= 26;
+    }
+    out.reverse();
+    String::from_utf8(out).expect("ASCII letters")
+}
@@ -0,0 +1,264 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
+
+const BLOCK: usize = 512;
+
+/// A regular file in an archive.
+pub struct Entry<'a> {
+    /// Path inside the archive, with `/` separators and no leading `./`.
+    pub name: String,
+    /// Uncompressed size, as the archive declares it.
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// The contents, decompressed as they are read, and never more than `size` bytes.
+    pub data: &'a mut dyn Read,
+}
+
+/// Calls `visit` with each regular file of a `.tar`, `.tar.gz`/`.tgz` or `.zip` archive, in
+/// archive order. The format is detected from the contents.
+///
+/// Nothing is decompressed that `visit` does not read, so entries can be left out by their
+/// declared size. Links, and entries with absolute paths or `..` components, are left out
+/// with a warning.
+pub fn read(path: &Path, visit: impl FnMut(Entry) -> Result<()>) -> Result<()> {
+    let mut file =
+        File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut magic = [0; 4];
+    let len = file.read(&mut magic)?;
+    drop(file);
+
+    match &magic[..len] {
+        [0x1f, 0x8b, ..] => {
+            let file = File::open(path)?;
+            read_tar(MultiGzDecoder::new(BufReader::new(file)), visit)
+        }
+        [b'P', b'K', 3, 4] | [b'P', b'K', 5, 6] => read_zip(&fs::read(path)?, visit),
+        _ => read_tar(BufReader::new(File::open(path)?), visit),
+    }
+    .with_context(|| format!("failed to read {}", path.display()))
+}
+
+/// `name` as an entry path, or `None` (with a warning) if it would escape the archive root.
+fn entry_name(name: &str) -> Option<String> {
+    let relative = name.trim_start_matches("./");
+    let safe = Path::new(relative)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if !safe {
+        eprintln!("Warning: skipping archive entry {name} outside the archive root");
+        return None;
+    }
+    Some(relative.to_string())
+}
+
+fn read_tar(mut reader: impl Read, mut visit: impl FnMut(Entry) -> Result<()>) -> Result<()> {
+    let mut header = [0; BLOCK];
+    // Names from a preceding GNU `L` entry or pax `path` record.
+    let mut long_name: Option<String> = None;
+    loop {
+        if !read_block(&mut reader, &mut header)? || header.iter().all(|&byte| byte == 0) {
+            return Ok(());
+        }
+        let size = tar_number(&header[124..136])?;
+        let padded = size.div_ceil(BLOCK as u64) * BLOCK as u64;
+        let mut data = (&mut reader).take(size);
+
+        let name = match long_name.take() {
+            Some(name) => name,
+            None => {
+                let name = tar_string(&header[0..100]);
+                let prefix = tar_string(&header[345..500]);
+                if &header[257..262] == b"ustar" && !prefix.is_empty() {
+                    format!("{prefix}/{name}")
+                } else {
+                    name
+                }
+            }
+        };
+
+        match header[156] {
+            b'0' | b'\0' | b'7' if !name.ends_with('/') => {
+                if let Some(name) = entry_name(&name) {
+                    let mtime = tar_number(&header[136..148]).unwrap_or(0);
+                    visit(Entry {
+                        name,
+                        size,
+                        modified: Some(UNIX_EPOCH + Duration::from_secs(mtime)),
+                        data: &mut data,
+                    })?;
+                }
+            }
+            b'L' => {
+                let mut name = Vec::new();
+                data.read_to_end(&mut name)?;
+                long_name = Some(tar_string(&name));
+            }
+            b'x' => {
+                let mut records = Vec::new();
+                data.read_to_end(&mut records)?;
+                long_name = pax_path(&String::from_utf8_lossy(&records));
+            }
+            b'1' | b'2' => eprintln!("Warning: skipping link {name} in archive"),
+            // Directories, global pax headers, devices, FIFOs and the like carry nothing to
+            // dump.
+            _ => {}
+        }
+        io::copy(&mut data, &mut io::sink())?;
+        io::copy(&mut (&mut reader).take(padded - size), &mut io::sink())?;
+    }
+}
+
+/// Fills `block`, returning `false` at a clean end of input.
+fn read_block(reader: &mut impl Read, block: &mut [u8; BLOCK]) -> Result<bool> {
+    let mut filled = 0;
+    while filled < BLOCK {
+        match reader.read(&mut block[filled..])? {
+            0 if filled == 0 => return Ok(false),
+            0 => bail!("truncated tar header"),
+            read => filled += read,
+        }
+    }
+    Ok(true)
+}
+
+fn tar_string(field: &[u8]) -> String {
+    let end = field
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// An octal field, or a base-256 one for values too large for octal.
+fn tar_number(field: &[u8]) -> Result<u64> {
+    if field[0] & 0x80 != 0 {
+        return Ok(field[1..]
+            .iter()
+            .fold(u64::from(field[0] & 0x7f), |value, &byte| {
+                (value << 8) | u64::from(byte)
+            }));
+    }
+    let text = tar_string(field);
+    let text = text.trim_matches(|c: char| c == ' ' || c == '\0');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).with_context(|| format!("invalid tar number {text:?}"))
+}
+
+/// The `path` of pax extended header records (`<len> path=<value>\n`).
+fn pax_path(records: &str) -> Option<String> {
+    records.lines().find_map(|record| {
+        let (_, field) = record.split_once(' ')?;
+        field.strip_prefix("path=").map(str::to_string)
+    })
+}
+
+fn read_zip(data: &[u8], mut visit: impl FnMut(Entry) -> Result<()>) -> Result<()> {
+    let u16_at = |offset: usize| -> Result<u16> {
+        let bytes = data.get(offset..offset + 2).context("truncated zip file")?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    };
+    let u32_at = |offset: usize| -> Result<u32> {
+        let bytes = data.get(offset..offset + 4).context("truncated zip file")?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+
+    // The end of central directory record is last, before a comment of up to 64 KiB.
+    let search_from = data.len().saturating_sub(22 + 0xffff);
+    let Some(end) = (search_from..data.len().saturating_sub(21))
+        .rev()
+        .find(|&offset| data[offset..offset + 4] == [b'P', b'K', 5, 6])
+    else {
+        bail!("no zip central directory found");
+    };
+    let entries = u16_at(end + 10)?;
+    let directory = u32_at(end + 16)?;
+    if directory == u32::MAX {
+        bail!("zip64 archives are not supported");
+    }
+    let mut offset = directory as usize;
+
+    for _ in 0..entries {
+        if u32_at(offset)? != 0x0201_4b50 {
+            bail!("corrupt zip central directory");
+        }
+        let flags = u16_at(offset + 8)?;
+        let method = u16_at(offset + 10)?;
+        let modified = dos_time(u16_at(offset + 14)?, u16_at(offset + 12)?);
+        let compressed_size = u32_at(offset + 20)? as usize;
+        let size = u64::from(u32_at(offset + 24)?);
+        let name_len = u16_at(offset + 28)? as usize;
+        let extra_len = u16_at(offset + 30)? as usize;
+        let comment_len = u16_at(offset + 32)? as usize;
+        let mode = u32_at(offset + 38)? >> 16;
+        let local = u32_at(offset + 42)? as usize;
+        let name = data
+            .get(offset + 46..offset + 46 + name_len)
+            .context("truncated zip file")?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        offset += 46 + name_len + extra_len + comment_len;
+
+        if name.ends_with('/') {
+            continue;
+        }
+        if mode & 0o170000 == 0o120000 {
+            eprintln!("Warning: skipping link {name} in archive");
+            continue;
+        }
+        if flags & 1 != 0 {
+            eprintln!("Warning: skipping encrypted {name} in archive");
+            continue;
+        }
+        let Some(entry_name) = entry_name(&name) else {
+            continue;
+        };
+
+        let start = local + 30 + u16_at(local + 26)? as usize + u16_at(local + 28)? as usize;
+        let contents = data
+            .get(start..start + compressed_size)
+            .context("truncated zip file")?;
+        let mut stored;
+        let mut deflated;
+        let data: &mut dyn Read = match method {
+            0 => {
+                stored = contents.take(size);
+                &mut stored
+            }
+            8 => {
+                deflated = DeflateDecoder::new(contents).take(size);
+                &mut deflated
+            }
+            _ => {
+                eprintln!("Warning: skipping {name} in archive (compression method {method})");
+                continue;
+            }
+        };
+        visit(Entry {
+            name: entry_name,
+            size,
+            modified,
+            data,
+        })?;
+    }
+    Ok(())
+}
+
+/// A zip entry's MS-DOS date and time, which have no time zone; taken as UTC.
+fn dos_time(date: u16, time: u16) -> Option<SystemTime> {
+    let days = crate::recency::days_from_civil(
+        1980 + i64::from(date >> 9),
+        i64::from((date >> 5) & 0xf),
+        i64::from(date & 0x1f),
+    );
+    let seconds = days * 86_400
+        + i64::from(time >> 11) * 3600
+        + i64::from((time >> 5) & 0x3f) * 60
+        + i64::from(time & 0x1f) * 2;
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(seconds).ok()?))
+}
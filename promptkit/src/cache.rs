@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tiktoken_rs::CoreBPE;
+
+use crate::{FileDump, TOKENIZER_NAME};
+
+/// Entries not used for this long are dropped when the cache is saved.
+const MAX_AGE_DAYS: u64 = 30;
+
+static CACHE: OnceLock<TokenCache> = OnceLock::new();
+
+/// Token counts of files from previous runs, in `<cache dir>/promptkit/tokens-<tokenizer>.json`.
+///
+/// Counts are keyed by path, modification time and size, so an unchanged file is not read
+/// again when only its count is needed. Contents that were redacted, `--strip`ped or changed
+/// otherwise after reading have no [`Source`] and are always counted.
+struct TokenCache {
+    path: PathBuf,
+    /// Absolute path to the file's metadata and tokens when it was last counted.
+    entries: Mutex<HashMap<String, Entry>>,
+    /// Entries added or touched on a new day since the last save.
+    changes: AtomicUsize,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct Entry {
+    /// Modification time, in nanoseconds since the Unix epoch.
+    modified: u64,
+    size: u64,
+    tokens: usize,
+    /// Day last used.
+    used: u64,
+}
+
+/// The file on disk whose contents a [`FileDump`](crate::FileDump) holds unchanged.
+#[derive(Clone, Debug)]
+pub struct Source {
+    path: String,
+    modified: u64,
+    pub size: u64,
+}
+
+impl Source {
+    /// `None` if the modification time is unknown or the path is not UTF-8.
+    pub fn new(path: &Path, metadata: &Metadata) -> Option<Self> {
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Source {
+            path: path.to_str()?.to_string(),
+            modified: modified.as_nanos() as u64,
+            size: metadata.len(),
+        })
+    }
+}
+
+/// Loads the cache, so that [`count`] uses it for the rest of the run. A missing or corrupt
+/// cache file starts an empty cache.
+pub fn enable() {
+    let Some(path) = dirs::cache_dir().map(|dir| {
+        dir.join("promptkit")
+            .join(format!("tokens-{TOKENIZER_NAME}.json"))
+    }) else {
+        return;
+    };
+    let entries = fs::read_to_string(&path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default();
+    let _ = CACHE.set(TokenCache {
+        path,
+        entries: Mutex::new(entries),
+        changes: AtomicUsize::new(0),
+    });
+}
+
+/// The cached tokens of `source`, if the cache is enabled and the file has not changed since
+/// it was counted.
+pub fn cached(source: &Source) -> Option<usize> {
+    let cache = CACHE.get()?;
+    let mut entries = cache.entries.lock().expect("cache lock");
+    let entry = entries
+        .get_mut(&source.path)
+        .filter(|entry| entry.modified == source.modified && entry.size == source.size)?;
+    let today = today();
+    if entry.used != today {
+        entry.used = today;
+        cache.changes.fetch_add(1, Ordering::Relaxed);
+    }
+    Some(entry.tokens)
+}
+
+/// Tokens in the contents of a file, from the cache if they are those of an unchanged file
+/// it has seen.
+pub fn count(tokenizer: &CoreBPE, file: &FileDump) -> usize {
+    let Some(source) = &file.source else {
+        return crate::count_tokens(tokenizer, &file.contents);
+    };
+    if let Some(tokens) = cached(source) {
+        return tokens;
+    }
+
+    let tokens = crate::count_tokens(tokenizer, &file.contents);
+    if let Some(cache) = CACHE.get() {
+        let entry = Entry {
+            modified: source.modified,
+            size: source.size,
+            tokens,
+            used: today(),
+        };
+        cache
+            .entries
+            .lock()
+            .expect("cache lock")
+            .insert(source.path.clone(), entry);
+        cache.changes.fetch_add(1, Ordering::Relaxed);
+    }
+    tokens
+}
+
+/// Writes the cache back if it changed, dropping entries unused for
+/// [`MAX_AGE_DAYS`].
+pub fn save() -> Result<()> {
+    let Some(cache) = CACHE.get() else {
+        return Ok(());
+    };
+    if cache.changes.swap(0, Ordering::Relaxed) == 0 {
+        return Ok(());
+    }
+    let oldest = today().saturating_sub(MAX_AGE_DAYS);
+    let entries: HashMap<String, Entry> = cache
+        .entries
+        .lock()
+        .expect("cache lock")
+        .iter()
+        .filter(|(_, entry)| entry.used >= oldest)
+        .map(|(path, entry)| (path.clone(), *entry))
+        .collect();
+
+    if let Some(dir) = cache.path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create directory {}", dir.display()))?;
+    }
+    fs::write(&cache.path, serde_json::to_string(&entries)?)
+        .with_context(|| format!("failed to write {}", cache.path.display()))?;
+    Ok(())
+}
+
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 86_400)
+}
@@ -0,0 +1,31 @@
+use std::ops::Range;
+
+/// Position of a prompt within a chunked dump (1-based).
+#[derive(Clone, Copy)]
+pub struct Part {
+    pub index: usize,
+    pub count: usize,
+}
+
+/// Greedily packs consecutive files into chunks whose summed cost stays within `budget`.
+///
+/// A file that alone exceeds the budget still gets a chunk of its own.
+pub fn plan(costs: &[usize], budget: usize) -> Vec<Range<usize>> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut used = 0;
+
+    for (idx, &cost) in costs.iter().enumerate() {
+        if idx > start && used + cost > budget {
+            chunks.push(start..idx);
+            start = idx;
+            used = 0;
+        }
+        used += cost;
+    }
+    if start < costs.len() || chunks.is_empty() {
+        chunks.push(start..costs.len());
+    }
+
+    chunks
+}
@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use crate::{Collection, FileDump};
+
+/// Lines of unchanged context around each hunk, as in `diff -u`.
+const CONTEXT: usize = 3;
+/// Past this many differing lines, a file's diff replaces all of it rather than searching
+/// for the shortest edit, which takes quadratic memory in the number of edits.
+const MAX_EDITS: usize = 2000;
+
+/// How a file differs between the two versions of a `--compare`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// Only in the new version; the dump holds its contents.
+    Added,
+    /// Only in the old version; the dump holds its old contents.
+    Removed,
+    /// In both, with different contents; the dump holds a unified diff.
+    Modified,
+}
+
+impl Change {
+    pub fn label(self) -> &'static str {
+        match self {
+            Change::Added => "added",
+            Change::Removed => "removed",
+            Change::Modified => "modified",
+        }
+    }
+}
+
+/// Merges the collections of two versions of a directory into one whose files are those
+/// that changed, and returns it with the paths of the files that did not.
+pub fn merge(old: Collection, new: Collection) -> (Collection, Vec<String>) {
+    let mut old_files: BTreeMap<String, FileDump> = old
+        .files
+        .into_iter()
+        .map(|file| (file.relative_path.clone(), file))
+        .collect();
+
+    let mut files = Vec::new();
+    let mut unchanged = Vec::new();
+    for mut file in new.files {
+        match old_files.remove(&file.relative_path) {
+            Some(before) if before.contents == file.contents => {
+                unchanged.push(file.relative_path);
+            }
+            Some(before) => {
+                file.contents = unified_diff(&file.relative_path, &before.contents, &file.contents);
+                file.source = None;
+                file.language = Some("diff".to_string());
+                file.truncated_from = file.truncated_from.or(before.truncated_from);
+                file.change = Some(Change::Modified);
+                files.push(file);
+            }
+            None => {
+                file.change = Some(Change::Added);
+                files.push(file);
+            }
+        }
+    }
+    files.extend(old_files.into_values().map(|mut file| {
+        file.change = Some(Change::Removed);
+        file
+    }));
+    files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    unchanged.sort();
+
+    // A file skipped on one side only is still reported once.
+    let mut skipped = new.skipped;
+    skipped.extend(old.skipped);
+    skipped.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    skipped.dedup_by(|a, b| a.relative_path == b.relative_path);
+    skipped.retain(|file| {
+        files
+            .binary_search_by(|kept| kept.relative_path.cmp(&file.relative_path))
+            .is_err()
+            && unchanged.binary_search(&file.relative_path).is_err()
+    });
+
+    let mut filtered = new.filtered;
+    filtered.extend(old.filtered);
+    filtered.sort();
+    filtered.dedup();
+
+    let mut sizes = old.sizes;
+    sizes.extend(new.sizes);
+
+    let collection = Collection {
+        files,
+        skipped,
+        symlinks: new.symlinks,
+        filtered,
+        sizes,
+    };
+    (collection, unchanged)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Keep,
+    Delete,
+    Insert,
+}
+
+/// A `diff -u` style diff of `old` to `new`, with `a/` and `b/` paths.
+fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let edits = edits(&old, &new);
+
+    let mut out = format!("--- a/{path}\n+++ b/{path}\n");
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|&idx| edits[idx] != Edit::Keep)
+        .collect();
+    let mut idx = 0;
+    while idx < changes.len() {
+        // Changes whose contexts touch or overlap share a hunk.
+        let mut last = idx;
+        while last + 1 < changes.len() && changes[last + 1] - changes[last] <= 2 * CONTEXT + 1 {
+            last += 1;
+        }
+        let start = changes[idx].saturating_sub(CONTEXT);
+        let end = (changes[last] + CONTEXT + 1).min(edits.len());
+        write_hunk(&mut out, &edits, start..end, &old, &new);
+        idx = last + 1;
+    }
+    out
+}
+
+fn write_hunk(
+    out: &mut String,
+    edits: &[Edit],
+    range: std::ops::Range<usize>,
+    old: &[&str],
+    new: &[&str],
+) {
+    let count = |edits: &[Edit], skip: Edit| edits.iter().filter(|&&edit| edit != skip).count();
+    let mut old_line = count(&edits[..range.start], Edit::Insert);
+    let mut new_line = count(&edits[..range.start], Edit::Delete);
+    let old_len = count(&edits[range.clone()], Edit::Insert);
+    let new_len = count(&edits[range.clone()], Edit::Delete);
+    // An empty side is numbered by the line before it, as in `diff -u`.
+    let first = |line: usize, len: usize| if len == 0 { line } else { line + 1 };
+    let _ = writeln!(
+        out,
+        "@@ -{},{} +{},{} @@",
+        first(old_line, old_len),
+        old_len,
+        first(new_line, new_len),
+        new_len
+    );
+    for &edit in &edits[range] {
+        match edit {
+            Edit::Keep => {
+                let _ = writeln!(out, " {}", old[old_line]);
+                old_line += 1;
+                new_line += 1;
+            }
+            Edit::Delete => {
+                let _ = writeln!(out, "-{}", old[old_line]);
+                old_line += 1;
+            }
+            Edit::Insert => {
+                let _ = writeln!(out, "+{}", new[new_line]);
+                new_line += 1;
+            }
+        }
+    }
+}
+
+/// A shortest edit script from `old` to `new` (Myers' algorithm), or a wholesale
+/// replacement when they differ in more than [`MAX_EDITS`] lines.
+fn edits(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let mut edits = vec![Edit::Keep; prefix];
+    match shortest_edits(a, b) {
+        Some(middle) => edits.extend(middle),
+        None => {
+            edits.extend(std::iter::repeat_n(Edit::Delete, a.len()));
+            edits.extend(std::iter::repeat_n(Edit::Insert, b.len()));
+        }
+    }
+    edits.extend(std::iter::repeat_n(Edit::Keep, suffix));
+    edits
+}
+
+fn shortest_edits(a: &[&str], b: &[&str]) -> Option<Vec<Edit>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (a.len() + b.len()).min(MAX_EDITS) as isize;
+    // `v[k + offset]` is the furthest `x` reached on diagonal `k = x - y`.
+    let offset = max + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    // The diagonals `-d..=d` of `v` after each step `d`, to walk the path back.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+                return Some(backtrack(&trace, n, m));
+            }
+        }
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+    }
+    None
+}
+
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..trace.len() as isize).rev() {
+        let previous = &trace[d as usize - 1];
+        let furthest = |k: isize| previous[(k + d - 1) as usize];
+        let k = x - y;
+        let down = k == -d || (k != d && furthest(k - 1) < furthest(k + 1));
+        let prev_k = if down { k + 1 } else { k - 1 };
+        let prev_x = furthest(prev_k);
+        let prev_y = prev_x - prev_k;
+        let (mid_x, mid_y) = if down {
+            (prev_x, prev_y + 1)
+        } else {
+            (prev_x + 1, prev_y)
+        };
+        while x > mid_x && y > mid_y {
+            edits.push(Edit::Keep);
+            x -= 1;
+            y -= 1;
+        }
+        edits.push(if down { Edit::Insert } else { Edit::Delete });
+        (x, y) = (prev_x, prev_y);
+    }
+    edits.extend(std::iter::repeat_n(Edit::Keep, x as usize));
+    edits.reverse();
+    edits
+}
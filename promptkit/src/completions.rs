@@ -0,0 +1,35 @@
+use std::io::{self, Write};
+
+use clap::Command;
+use clap_complete::Shell;
+use clap_mangen::Man;
+
+/// Writes the completion script of `cmd` for `shell`.
+pub fn generate(shell: Shell, mut cmd: Command, out: &mut dyn Write) -> io::Result<()> {
+    let bin = cmd.get_bin_name().unwrap_or(cmd.get_name()).to_string();
+    // `clap_complete` panics on write errors, such as a closed pipe, so buffer the script.
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut cmd, bin, &mut script);
+    out.write_all(&script)
+}
+
+/// Writes a roff man page for `cmd`, followed by one for each of its subcommands and theirs.
+pub fn man(mut cmd: Command, out: &mut dyn Write) -> io::Result<()> {
+    cmd.build();
+    // Subcommand pages name the program they come from, not themselves.
+    let source = format!(
+        "{} {}",
+        cmd.get_name(),
+        cmd.get_version().unwrap_or_default()
+    );
+    Man::new(cmd.clone()).render(out)?;
+    man_subcommands(&cmd, &source, out)
+}
+
+fn man_subcommands(cmd: &Command, source: &str, out: &mut dyn Write) -> io::Result<()> {
+    for sub in cmd.get_subcommands().filter(|sub| sub.get_name() != "help") {
+        Man::new(sub.clone()).source(source).render(out)?;
+        man_subcommands(sub, source, out)?;
+    }
+    Ok(())
+}
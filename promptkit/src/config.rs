@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+/// User configuration, read from `<config dir>/promptkit/config.toml` or `--config`.
+///
+/// ```toml
+/// # Record each `dump` under `<data dir>/promptkit/history.jsonl` for `history` and `rerun`.
+/// record_history = true
+///
+/// [languages]
+/// # Fence language by extension or by file name; overrides the built-in table.
+/// proto = "protobuf"
+/// "Tiltfile" = "python"
+///
+/// [prices]
+/// # USD per million input tokens, by model-name prefix; overrides the built-in table.
+/// "my-finetune" = 0.8
+///
+/// [max_file_sizes]
+/// # `--max-file-size` by glob (gitignore syntax); `--max-file-size` patterns take precedence.
+/// "*.json" = "8k"
+/// "src/**" = 262144
+///
+/// [preprocessors]
+/// # Shell command by glob; it gets the file on stdin (and its path in `$PROMPTKIT_FILE`) and
+/// # its stdout replaces the contents. The first matching glob in sorted order is used.
+/// "*.pb" = "protoc --decode_raw"
+/// "*.min.js" = "js-beautify"
+///
+/// [hooks]
+/// # Shell commands run in the current directory before and after each `dump`. `post-dump`
+/// # gets the prompt on stdin, and `$PROMPTKIT_OUTPUT`, `$PROMPTKIT_TOKENS`,
+/// # `$PROMPTKIT_FILES_INCLUDED` and the like in its environment.
+/// pre-dump = "cargo doc --no-deps"
+/// post-dump = "aws s3 cp \"$PROMPTKIT_OUTPUT\" s3://prompts/"
+///
+/// [profiles]
+/// # Flags applied by `dump -P <name>`, before the ones given on the command line.
+/// backend = ["--include", "services/api/**", "--exclude", "*_test.go", "--max-tokens", "100000"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub record_history: bool,
+    pub languages: BTreeMap<String, String>,
+    pub prices: BTreeMap<String, f64>,
+    pub profiles: BTreeMap<String, Vec<String>>,
+    pub max_file_sizes: BTreeMap<String, Size>,
+    pub preprocessors: BTreeMap<String, String>,
+    pub hooks: Hooks,
+}
+
+/// Commands run around each `dump`; a failing hook fails the dump.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Hooks {
+    pub pre_dump: Option<String>,
+    pub post_dump: Option<String>,
+}
+
+/// A size in bytes, or a string like `"8k"`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Size {
+    Bytes(usize),
+    Text(String),
+}
+
+impl Size {
+    pub fn bytes(&self) -> Result<usize, String> {
+        match self {
+            Size::Bytes(bytes) => Ok(*bytes),
+            Size::Text(text) => crate::parse_size(text),
+        }
+    }
+}
+
+impl Config {
+    /// Loads `path`, or the default config file if it exists.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match default_path() {
+                Some(path) if path.is_file() => path,
+                _ => return Ok(Config::default()),
+            },
+        };
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read config {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("invalid config {}", path.display()))
+    }
+
+    /// Flags of the profile `name`.
+    pub fn profile(&self, name: &str) -> Result<&[String]> {
+        match self.profiles.get(name) {
+            Some(args) => Ok(args),
+            None if self.profiles.is_empty() => {
+                bail!("unknown profile {name}; no profiles are defined in the config")
+            }
+            None => bail!(
+                "unknown profile {name}; available: {}",
+                self.profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("promptkit"))
+}
+
+fn default_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
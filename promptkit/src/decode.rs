@@ -0,0 +1,146 @@
+/// How a file that was not plain UTF-8 got decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    Windows1252,
+    /// UTF-8 with invalid sequences replaced by U+FFFD (`--lossy-utf8`).
+    LossyUtf8,
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8Bom => "utf-8-bom",
+            Encoding::Utf16Le => "utf-16le",
+            Encoding::Utf16Be => "utf-16be",
+            Encoding::Windows1252 => "windows-1252",
+            Encoding::LossyUtf8 => "utf-8-lossy",
+        }
+    }
+}
+
+/// Bytes 0x80..=0x9F in Windows-1252; NUL marks the five undefined ones.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\0', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\0', 'Ž', '\0', '\0', '‘',
+    '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\0', 'ž', 'Ÿ',
+];
+
+/// Decodes file contents as text.
+///
+/// Plain UTF-8 comes back with no encoding. Otherwise a BOM selects UTF-8 or UTF-16, BOM-less
+/// UTF-16 is recognized by its pattern of zero bytes, and text that looks like a single-byte
+/// legacy encoding is read as Windows-1252 (a superset of Latin-1's printable range). With
+/// `lossy`, anything else without NUL bytes is decoded with replacement characters. Returns
+/// `None` for binary data.
+pub fn decode(data: Vec<u8>, lossy: bool) -> Option<(String, Option<Encoding>)> {
+    if let Some(rest) = data.strip_prefix(b"\xEF\xBB\xBF") {
+        return String::from_utf8(rest.to_vec())
+            .ok()
+            .map(|text| (text, Some(Encoding::Utf8Bom)));
+    }
+    if let Some(rest) = data.strip_prefix(b"\xFF\xFE") {
+        return utf16(rest, u16::from_le_bytes).map(|text| (text, Some(Encoding::Utf16Le)));
+    }
+    if let Some(rest) = data.strip_prefix(b"\xFE\xFF") {
+        return utf16(rest, u16::from_be_bytes).map(|text| (text, Some(Encoding::Utf16Be)));
+    }
+
+    // ASCII-range UTF-16 is valid UTF-8 too (NUL is a valid character), so check it first.
+    if let Some(encoding) = bomless_utf16(&data) {
+        let from_bytes = match encoding {
+            Encoding::Utf16Le => u16::from_le_bytes,
+            _ => u16::from_be_bytes,
+        };
+        if let Some(text) = utf16(&data, from_bytes) {
+            return Some((text, Some(encoding)));
+        }
+    }
+
+    let data = match String::from_utf8(data) {
+        Ok(text) => return Some((text, None)),
+        Err(err) => err.into_bytes(),
+    };
+
+    if data.contains(&0) {
+        return None;
+    }
+    if let Some(text) = windows_1252(&data) {
+        return Some((text, Some(Encoding::Windows1252)));
+    }
+    lossy.then(|| {
+        (
+            String::from_utf8_lossy(&data).into_owned(),
+            Some(Encoding::LossyUtf8),
+        )
+    })
+}
+
+fn utf16(data: &[u8], from_bytes: fn([u8; 2]) -> u16) -> Option<String> {
+    if !data.len().is_multiple_of(2) {
+        return None;
+    }
+    let units = data
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]));
+    char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .ok()
+}
+
+/// Mostly-ASCII UTF-16 has a zero in every other byte, and nowhere else.
+fn bomless_utf16(data: &[u8]) -> Option<Encoding> {
+    if data.len() < 4 || !data.len().is_multiple_of(2) {
+        return None;
+    }
+    let pairs = data.len() / 2;
+    let zeros_at = |offset: usize| {
+        data.iter()
+            .skip(offset)
+            .step_by(2)
+            .filter(|&&b| b == 0)
+            .count()
+    };
+    let (even, odd) = (zeros_at(0), zeros_at(1));
+    if odd * 10 >= pairs * 7 && even == 0 {
+        Some(Encoding::Utf16Le)
+    } else if even * 10 >= pairs * 7 && odd == 0 {
+        Some(Encoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+/// Decodes `data` as Windows-1252 if it looks like single-byte text: no control characters
+/// besides whitespace, no undefined bytes, no valid multi-byte UTF-8 (that would be UTF-8 with
+/// a few bad bytes), and non-ASCII bytes mostly standing alone (multi-byte encodings such as
+/// GBK or Shift-JIS put them in runs).
+fn windows_1252(data: &[u8]) -> Option<String> {
+    if data.utf8_chunks().any(|chunk| !chunk.valid().is_ascii()) {
+        return None;
+    }
+    let high = data.iter().filter(|b| !b.is_ascii()).count();
+    let paired = data
+        .windows(2)
+        .filter(|pair| !pair[0].is_ascii() && !pair[1].is_ascii())
+        .count();
+    if paired * 2 > high {
+        return None;
+    }
+
+    let mut text = String::with_capacity(data.len() + high);
+    for &byte in data {
+        let c = match byte {
+            b'\t' | b'\n' | b'\r' | 0x0C => byte as char,
+            0x00..=0x1F | 0x7F => return None,
+            0x80..=0x9F => match WINDOWS_1252_HIGH[(byte - 0x80) as usize] {
+                '\0' => return None,
+                c => c,
+            },
+            _ => byte as char,
+        };
+        text.push(c);
+    }
+    Some(text)
+}
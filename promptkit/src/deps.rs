@@ -0,0 +1,331 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::FileDump;
+
+static RUST_MOD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+([A-Za-z_][A-Za-z0-9_]*)\s*;")
+        .expect("invalid import pattern")
+});
+static RUST_USE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?ms)^\s*(?:pub(?:\([^)]*\))?\s+)?use\s+([^;]+);").expect("invalid import pattern")
+});
+static PYTHON_IMPORT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*import\s+([A-Za-z0-9_., ]+)").expect("invalid import pattern")
+});
+static PYTHON_FROM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*from\s+(\.*)([A-Za-z0-9_.]*)\s+import\s+\(?\s*([A-Za-z0-9_, \n]+)")
+        .expect("invalid import pattern")
+});
+static JS_SPECIFIER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?:\bfrom\s*|\bimport\s*\(?\s*|\brequire\s*\(\s*)["'](\.{1,2}/[^"']*)["']"#)
+        .expect("invalid import pattern")
+});
+static GO_IMPORT_BLOCK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)\bimport\s*\(([^)]*)\)").expect("invalid import pattern"));
+static GO_IMPORT_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?m)^\s*import\s+(?:[A-Za-z_.]+\s+)?"([^"]+)""#).expect("invalid import pattern")
+});
+static QUOTED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#""([^"]+)""#).expect("invalid import pattern"));
+static GO_MODULE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\s*module\s+(\S+)").expect("invalid import pattern"));
+
+const JS_EXTENSIONS: [&str; 8] = ["ts", "tsx", "js", "jsx", "mjs", "cjs", "mts", "cts"];
+
+/// Paths of the files within `hops` import edges of the seeds, in either direction.
+///
+/// Imports are resolved from `mod`/`use` (Rust), `import`/`from` (Python), relative
+/// `import`/`require` (JS/TS) and module-local `import` (Go) statements.
+pub fn around(files: &[FileDump], seeds: &[String], hops: usize) -> BTreeSet<String> {
+    let graph = Graph::new(files);
+    let mut neighbors: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for (from, targets) in &graph.edges {
+        for to in targets {
+            neighbors.entry(from).or_default().insert(to);
+            neighbors.entry(to).or_default().insert(from);
+        }
+    }
+
+    let mut selected: BTreeSet<String> = seeds.iter().cloned().collect();
+    let mut queue: VecDeque<(String, usize)> = seeds.iter().map(|seed| (seed.clone(), 0)).collect();
+    while let Some((path, distance)) = queue.pop_front() {
+        if distance == hops {
+            continue;
+        }
+        for next in neighbors.get(path.as_str()).into_iter().flatten() {
+            if selected.insert(next.to_string()) {
+                queue.push_back((next.to_string(), distance + 1));
+            }
+        }
+    }
+    selected
+}
+
+/// Import edges between the files of a dump, keyed by relative path.
+struct Graph<'a> {
+    edges: BTreeMap<&'a str, BTreeSet<&'a str>>,
+}
+
+impl<'a> Graph<'a> {
+    fn new(files: &'a [FileDump]) -> Self {
+        let paths: BTreeMap<String, &'a str> = files
+            .iter()
+            .map(|file| {
+                (
+                    normalize(Path::new(&file.relative_path)),
+                    file.relative_path.as_str(),
+                )
+            })
+            .collect();
+        let go_module = files
+            .iter()
+            .find(|file| file.relative_path == "go.mod")
+            .and_then(|file| GO_MODULE.captures(&file.contents))
+            .map(|caps| caps[1].to_string());
+
+        let mut edges: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for file in files {
+            let candidates = match file.language.as_deref() {
+                Some("rust") => rust_imports(file, &paths),
+                Some("python") => python_imports(file),
+                Some("javascript" | "jsx" | "typescript" | "tsx") => js_imports(file),
+                Some("go") => go_imports(file, go_module.as_deref(), &paths),
+                _ => Vec::new(),
+            };
+            let targets: BTreeSet<&str> = candidates
+                .iter()
+                .filter_map(|candidate| paths.get(&normalize(candidate)).copied())
+                .filter(|target| *target != file.relative_path)
+                .collect();
+            if !targets.is_empty() {
+                edges.insert(&file.relative_path, targets);
+            }
+        }
+        Graph { edges }
+    }
+}
+
+/// Resolves `.` and `..` and joins components with `/`.
+fn normalize(path: &Path) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                parts.pop();
+            }
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            _ => {}
+        }
+    }
+    parts.join("/")
+}
+
+fn dir_of(relative_path: &str) -> PathBuf {
+    Path::new(relative_path)
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+}
+
+/// Candidate files for `mod x;` and `use crate::a::b` / `super::` / `self::` paths.
+fn rust_imports(file: &FileDump, paths: &BTreeMap<String, &str>) -> Vec<PathBuf> {
+    let path = Path::new(&file.relative_path);
+    let dir = dir_of(&file.relative_path);
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("");
+    // Directory holding this module's children.
+    let module_dir = match stem {
+        "main" | "lib" | "mod" => dir.clone(),
+        _ => dir.join(stem),
+    };
+    // Directory of the crate root: the nearest ancestor with a lib.rs or main.rs.
+    let crate_dir = dir
+        .ancestors()
+        .find(|ancestor| {
+            ["lib.rs", "main.rs"]
+                .iter()
+                .any(|root| paths.contains_key(&normalize(&ancestor.join(root))))
+        })
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| dir.clone());
+
+    let module_files = |base: &Path, segments: &[&str]| -> Vec<PathBuf> {
+        // Any prefix of the path may name the module file (`a::b::Item` lives in a/b.rs or a.rs).
+        (1..=segments.len())
+            .flat_map(|len| {
+                let module = segments[..len].iter().collect::<PathBuf>();
+                [
+                    base.join(&module).with_extension("rs"),
+                    base.join(&module).join("mod.rs"),
+                ]
+            })
+            .collect()
+    };
+
+    let mut candidates = Vec::new();
+    for caps in RUST_MOD.captures_iter(&file.contents) {
+        candidates.extend(module_files(&module_dir, &[&caps[1]]));
+    }
+    for caps in RUST_USE.captures_iter(&file.contents) {
+        for use_path in expand_use_groups(&caps[1]) {
+            let segments: Vec<&str> = use_path.split("::").map(str::trim).collect();
+            let (base, rest) = match segments.split_first() {
+                Some((&"crate", rest)) => (crate_dir.clone(), rest),
+                Some((&"self", rest)) => (module_dir.clone(), rest),
+                Some((&"super", rest)) => (
+                    if matches!(stem, "main" | "lib" | "mod") {
+                        dir.parent().map(Path::to_path_buf).unwrap_or_default()
+                    } else {
+                        dir.clone()
+                    },
+                    rest,
+                ),
+                _ => continue,
+            };
+            candidates.extend(module_files(&base, rest));
+        }
+    }
+    candidates
+}
+
+/// Expands one level of `{a, b::c}` groups: `crate::x::{a, b::c}` -> `crate::x::a`, `crate::x::b::c`.
+fn expand_use_groups(tree: &str) -> Vec<String> {
+    let tree: String = tree.split_whitespace().collect();
+    let Some(open) = tree.find('{') else {
+        return vec![tree];
+    };
+    let prefix = &tree[..open];
+    let inner = tree[open + 1..].trim_end_matches('}');
+
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in inner.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&inner[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&inner[start..]);
+    items
+        .into_iter()
+        .filter(|item| !item.is_empty())
+        .map(|item| format!("{prefix}{}", item.split('{').next().unwrap_or(item)))
+        .collect()
+}
+
+/// Candidate files for `import a.b` and `from .x import y`, relative to the root, `src/` and
+/// (for relative imports) the file's package.
+fn python_imports(file: &FileDump) -> Vec<PathBuf> {
+    let dir = dir_of(&file.relative_path);
+    let module_files = |base: &Path, module: &str| -> Vec<PathBuf> {
+        let module: PathBuf = module.split('.').filter(|part| !part.is_empty()).collect();
+        vec![
+            base.join(&module).with_extension("py"),
+            base.join(&module).join("__init__.py"),
+        ]
+    };
+    let absolute = |module: &str| -> Vec<PathBuf> {
+        [PathBuf::new(), PathBuf::from("src"), dir.clone()]
+            .iter()
+            .flat_map(|base| module_files(base, module))
+            .collect()
+    };
+
+    let mut candidates = Vec::new();
+    for caps in PYTHON_IMPORT.captures_iter(&file.contents) {
+        for module in caps[1].split(',') {
+            let module = module.split_whitespace().next().unwrap_or("");
+            candidates.extend(absolute(module));
+        }
+    }
+    for caps in PYTHON_FROM.captures_iter(&file.contents) {
+        let (dots, module) = (caps[1].len(), &caps[2]);
+        let names = caps[3]
+            .split(',')
+            .filter_map(|name| name.split_whitespace().next());
+        if dots == 0 {
+            candidates.extend(absolute(module));
+            candidates.extend(names.flat_map(|name| absolute(&format!("{module}.{name}"))));
+            continue;
+        }
+        let mut base = dir.clone();
+        for _ in 1..dots {
+            base.push("..");
+        }
+        candidates.extend(module_files(&base, module));
+        for name in names {
+            candidates.extend(module_files(&base, &format!("{module}.{name}")));
+        }
+    }
+    candidates
+}
+
+/// Candidate files for relative `import`/`export ... from`/`require()` specifiers.
+fn js_imports(file: &FileDump) -> Vec<PathBuf> {
+    let dir = dir_of(&file.relative_path);
+    let mut candidates = Vec::new();
+    for caps in JS_SPECIFIER.captures_iter(&file.contents) {
+        let target = dir.join(&caps[1]);
+        candidates.push(target.clone());
+        for ext in JS_EXTENSIONS {
+            let mut with_ext = target.clone().into_os_string();
+            with_ext.push(format!(".{ext}"));
+            candidates.push(with_ext.into());
+            candidates.push(target.join(format!("index.{ext}")));
+        }
+    }
+    candidates
+}
+
+/// All files of imported packages that belong to this module (per `go.mod`).
+fn go_imports(
+    file: &FileDump,
+    module: Option<&str>,
+    paths: &BTreeMap<String, &str>,
+) -> Vec<PathBuf> {
+    let Some(module) = module else {
+        return Vec::new();
+    };
+    let mut imports: Vec<String> = GO_IMPORT_LINE
+        .captures_iter(&file.contents)
+        .map(|caps| caps[1].to_string())
+        .collect();
+    for block in GO_IMPORT_BLOCK.captures_iter(&file.contents) {
+        imports.extend(
+            QUOTED
+                .captures_iter(&block[1])
+                .map(|caps| caps[1].to_string()),
+        );
+    }
+
+    let mut candidates = Vec::new();
+    for import in imports {
+        let Some(package_dir) = import.strip_prefix(module) else {
+            continue;
+        };
+        let package_dir = package_dir.trim_start_matches('/');
+        candidates.extend(
+            paths
+                .keys()
+                .filter(|path| {
+                    path.ends_with(".go")
+                        && !path.ends_with("_test.go")
+                        && dir_of(path) == Path::new(package_dir)
+                })
+                .map(PathBuf::from),
+        );
+    }
+    candidates
+}
@@ -0,0 +1,192 @@
+use std::fmt::Write as _;
+
+use anyhow::Result;
+
+use crate::chunk::Part;
+
+/// One file's portion of a unified diff, possibly only some of its hunks.
+pub struct DiffPiece {
+    pub path: String,
+    /// e.g. `hunks 3-5 of 9` when a file's diff was split.
+    pub label: Option<String>,
+    pub text: String,
+}
+
+struct FileDiff {
+    path: String,
+    header: String,
+    hunks: Vec<String>,
+}
+
+/// Splits a unified diff (`git diff` or plain `diff -u`) into per-file pieces.
+pub fn split_files(diff: &str) -> Vec<DiffPiece> {
+    parse(diff)
+        .into_iter()
+        .map(|file| DiffPiece {
+            text: file.header + &file.hunks.concat(),
+            path: file.path,
+            label: None,
+        })
+        .collect()
+}
+
+/// Like [`split_files`], but files whose cost exceeds `budget` are split further at hunk
+/// boundaries, repeating the file header in each piece.
+pub fn split_bounded(diff: &str, budget: usize, cost: impl Fn(&str) -> usize) -> Vec<DiffPiece> {
+    let mut pieces = Vec::new();
+    for file in parse(diff) {
+        let whole = file.header.clone() + &file.hunks.concat();
+        if cost(&whole) <= budget || file.hunks.len() <= 1 {
+            pieces.push(DiffPiece {
+                path: file.path,
+                label: None,
+                text: whole,
+            });
+            continue;
+        }
+
+        let header_cost = cost(&file.header);
+        let hunk_costs: Vec<usize> = file.hunks.iter().map(|hunk| cost(hunk)).collect();
+        let ranges = crate::chunk::plan(&hunk_costs, budget.saturating_sub(header_cost));
+        for range in ranges {
+            pieces.push(DiffPiece {
+                path: file.path.clone(),
+                label: Some(format!(
+                    "hunks {}-{} of {}",
+                    range.start + 1,
+                    range.end,
+                    file.hunks.len()
+                )),
+                text: file.header.clone() + &file.hunks[range].concat(),
+            });
+        }
+    }
+    pieces
+}
+
+fn parse(diff: &str) -> Vec<FileDiff> {
+    let lines: Vec<&str> = diff.split_inclusive('\n').collect();
+    let git_style = lines.iter().any(|line| line.starts_with("diff --git "));
+
+    let mut files: Vec<FileDiff> = Vec::new();
+    for (idx, line) in lines.iter().enumerate() {
+        let starts_file = if git_style {
+            line.starts_with("diff --git ")
+        } else {
+            line.starts_with("--- ")
+                && lines
+                    .get(idx + 1)
+                    .is_some_and(|next| next.starts_with("+++ "))
+        };
+
+        if starts_file {
+            files.push(FileDiff {
+                path: String::new(),
+                header: String::new(),
+                hunks: Vec::new(),
+            });
+        }
+        let Some(file) = files.last_mut() else {
+            // Preamble before the first file (e.g. `git format-patch` headers).
+            continue;
+        };
+
+        if line.starts_with("@@") {
+            file.hunks.push(String::new());
+        }
+        match file.hunks.last_mut() {
+            Some(hunk) => hunk.push_str(line),
+            None => file.header.push_str(line),
+        }
+    }
+
+    for file in &mut files {
+        file.path = path_from_header(&file.header);
+    }
+    files
+}
+
+fn path_from_header(header: &str) -> String {
+    let marker = |prefix: &str| {
+        header
+            .lines()
+            .find_map(|line| line.strip_prefix(prefix))
+            .map(|path| path.split('\t').next().unwrap_or(path).trim())
+            .filter(|path| *path != "/dev/null")
+            .map(|path| {
+                path.strip_prefix("a/")
+                    .or_else(|| path.strip_prefix("b/"))
+                    .unwrap_or(path)
+                    .to_string()
+            })
+    };
+    marker("+++ ")
+        .or_else(|| marker("--- "))
+        .or_else(|| {
+            header
+                .lines()
+                .next()
+                .and_then(|line| line.strip_prefix("diff --git "))
+                .and_then(|rest| rest.rsplit(" b/").next())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| "<unknown>".to_string())
+}
+
+/// Renders a self-contained review prompt for `pieces`.
+pub fn render(pieces: &[DiffPiece], task: &str, part: Part) -> Result<String> {
+    let mut prompt = String::new();
+
+    writeln!(
+        prompt,
+        "The following is part {} of {} of a diff. Each part is self-contained; do the task for the changes in this part.",
+        part.index, part.count
+    )?;
+    writeln!(prompt)?;
+
+    writeln!(prompt, "# Diff")?;
+    writeln!(prompt, "Files in this part:")?;
+    for piece in pieces {
+        let (added, removed) = line_counts(&piece.text);
+        write!(prompt, "- {} (+{} -{})", piece.path, added, removed)?;
+        if let Some(label) = &piece.label {
+            write!(prompt, " [{}]", label)?;
+        }
+        writeln!(prompt)?;
+    }
+    writeln!(prompt)?;
+
+    for piece in pieces {
+        match &piece.label {
+            Some(label) => writeln!(prompt, "## {} ({})", piece.path, label)?,
+            None => writeln!(prompt, "## {}", piece.path)?,
+        }
+        let fence = crate::fence_for(&piece.text);
+        writeln!(prompt, "{}diff", fence)?;
+        prompt.push_str(&piece.text);
+        if !piece.text.ends_with('\n') {
+            prompt.push('\n');
+        }
+        writeln!(prompt, "{}", fence)?;
+        writeln!(prompt)?;
+    }
+
+    writeln!(prompt, "# Task")?;
+    writeln!(prompt, "{}", task.trim_end())?;
+    writeln!(prompt)?;
+
+    Ok(prompt)
+}
+
+fn line_counts(text: &str) -> (usize, usize) {
+    let mut added = 0;
+    let mut removed = 0;
+    for line in text.lines() {
+        if line.starts_with('+') && !line.starts_with("+++ ") {
+            added += 1;
+        } else if line.starts_with('-') && !line.starts_with("--- ") {
+            removed += 1;
+        }
+    }
+    (added, removed)
+}
@@ -0,0 +1,112 @@
+use std::env::consts;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use anyhow::Result;
+
+/// Toolchains whose version is reported, as a label and the command that prints it. The
+/// first command of a label that runs is used.
+const TOOLCHAINS: &[(&str, &[&str])] = &[
+    ("rustc", &["rustc", "--version"]),
+    ("cargo", &["cargo", "--version"]),
+    ("node", &["node", "--version"]),
+    ("npm", &["npm", "--version"]),
+    ("python", &["python3", "--version"]),
+    ("python", &["python", "--version"]),
+    ("go", &["go", "version"]),
+];
+
+/// Renders the `## Environment` section: OS, architecture and the versions of the
+/// toolchains found on `PATH`. Commands run in `root`, so per-project toolchain pins apply.
+pub fn describe(root: &Path) -> Result<String> {
+    let versions: Vec<(&str, Option<String>)> = thread::scope(|scope| {
+        let handles: Vec<_> = TOOLCHAINS
+            .iter()
+            .map(|&(label, command)| (label, scope.spawn(move || version(root, command))))
+            .collect();
+        handles
+            .into_iter()
+            .map(|(label, handle)| (label, handle.join().ok().flatten()))
+            .collect()
+    });
+
+    let mut section = String::new();
+    writeln!(section, "## Environment")?;
+    match os_version() {
+        Some(version) => writeln!(section, "OS: {} ({})", version, consts::OS)?,
+        None => writeln!(section, "OS: {}", consts::OS)?,
+    }
+    writeln!(section, "Architecture: {}", consts::ARCH)?;
+    let mut reported = Vec::new();
+    for (label, version) in versions {
+        if reported.contains(&label) {
+            continue;
+        }
+        if let Some(version) = version {
+            writeln!(section, "{label}: {}", strip_name(&version, label))?;
+            reported.push(label);
+        }
+    }
+    if reported.is_empty() {
+        writeln!(section, "No toolchains found.")?;
+    }
+    Ok(section)
+}
+
+/// The first line of a command's output, or `None` if it is not installed or fails.
+fn version(root: &Path, command: &[&str]) -> Option<String> {
+    let output = Command::new(command[0])
+        .args(&command[1..])
+        .current_dir(root)
+        .stdin(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    // Older Pythons print their version on stderr.
+    let text = if output.stdout.is_empty() {
+        output.stderr
+    } else {
+        output.stdout
+    };
+    let line = String::from_utf8_lossy(&text)
+        .lines()
+        .next()?
+        .trim()
+        .to_string();
+    (!line.is_empty()).then_some(line)
+}
+
+/// `rustc 1.85.0 (...)` as `1.85.0 (...)`, and `go version go1.24.0 linux/amd64` as
+/// `go1.24.0 linux/amd64`.
+fn strip_name<'a>(version: &'a str, label: &str) -> &'a str {
+    for prefix in [format!("{label} version "), format!("{label} ")] {
+        if let Some(head) = version.get(..prefix.len())
+            && head.eq_ignore_ascii_case(&prefix)
+        {
+            return &version[prefix.len()..];
+        }
+    }
+    version
+}
+
+/// The distribution or release name, e.g. `Ubuntu 24.04.1 LTS` or `macOS 15.2`.
+fn os_version() -> Option<String> {
+    match consts::OS {
+        "linux" => {
+            let release = fs::read_to_string("/etc/os-release").ok()?;
+            let name = release
+                .lines()
+                .find_map(|line| line.strip_prefix("PRETTY_NAME="))?;
+            Some(name.trim_matches('"').to_string())
+        }
+        "macos" => {
+            let version = version(Path::new("/"), &["sw_vers", "-productVersion"])?;
+            Some(format!("macOS {version}"))
+        }
+        "windows" => version(Path::new("/"), &["cmd", "/C", "ver"]),
+        _ => None,
+    }
+}
@@ -0,0 +1,163 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use rusqlite::{Connection, params};
+
+use crate::{FileDump, SkippedFile};
+
+const COLUMNS: [&str; 8] = [
+    "run", "root", "path", "status", "reason", "bytes", "tokens", "language",
+];
+
+/// An `--export FORMAT:PATH` target.
+#[derive(Clone, Debug)]
+pub struct Export {
+    format: Format,
+    pub path: PathBuf,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Format {
+    Csv,
+    Sqlite,
+}
+
+pub fn parse_export(arg: &str) -> Result<Export, String> {
+    let (format, path) = match arg.split_once(':') {
+        Some(("csv", path)) => (Format::Csv, path),
+        Some(("sqlite", path)) => (Format::Sqlite, path),
+        _ => return Err(format!("expected csv:PATH or sqlite:PATH, got {arg:?}")),
+    };
+    if path.is_empty() {
+        return Err(format!("missing path in {arg:?}"));
+    }
+    Ok(Export {
+        format,
+        path: PathBuf::from(path),
+    })
+}
+
+/// One row per collected file. Skipped files have the size they had when skipped and no
+/// token count.
+struct Row<'a> {
+    path: &'a str,
+    status: &'static str,
+    reason: Option<&'static str>,
+    bytes: Option<u64>,
+    tokens: Option<usize>,
+    language: Option<&'a str>,
+}
+
+/// Appends a row per included and skipped file to the export, all with the same `run`
+/// (seconds since the Unix epoch) so successive dumps can be compared.
+pub fn write(
+    export: &Export,
+    run: u64,
+    root: &str,
+    files: &[FileDump],
+    file_tokens: &[usize],
+    skipped: &[SkippedFile],
+) -> Result<()> {
+    let included = files.iter().zip(file_tokens).map(|(file, &tokens)| Row {
+        path: &file.relative_path,
+        status: "included",
+        reason: None,
+        bytes: Some(file.contents.len() as u64),
+        tokens: Some(tokens),
+        language: file.language.as_deref(),
+    });
+    let skipped = skipped.iter().map(|file| Row {
+        path: &file.relative_path,
+        status: "skipped",
+        reason: Some(file.reason.kind()),
+        bytes: file.bytes,
+        tokens: None,
+        language: None,
+    });
+    let rows: Vec<Row> = included.chain(skipped).collect();
+
+    match export.format {
+        Format::Csv => write_csv(&export.path, run, root, &rows),
+        Format::Sqlite => write_sqlite(&export.path, run, root, &rows),
+    }
+    .with_context(|| format!("failed to export to {}", export.path.display()))
+}
+
+fn write_csv(path: &Path, run: u64, root: &str, rows: &[Row]) -> Result<()> {
+    let is_new = fs::metadata(path).map_or(true, |meta| meta.len() == 0);
+    let mut out = String::new();
+    if is_new {
+        out.push_str(&COLUMNS.join(","));
+        out.push('\n');
+    }
+    for row in rows {
+        let fields = [
+            run.to_string(),
+            csv_field(root),
+            csv_field(row.path),
+            row.status.to_string(),
+            row.reason.unwrap_or_default().to_string(),
+            row.bytes.map(|bytes| bytes.to_string()).unwrap_or_default(),
+            row.tokens
+                .map(|tokens| tokens.to_string())
+                .unwrap_or_default(),
+            csv_field(row.language.unwrap_or_default()),
+        ];
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(out.as_bytes())?;
+    Ok(())
+}
+
+/// Quotes a field if it contains a comma, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Appends to the `files` table, creating the database and table if needed.
+fn write_sqlite(path: &Path, run: u64, root: &str, rows: &[Row]) -> Result<()> {
+    let mut db = Connection::open(path)?;
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS files (
+            run INTEGER NOT NULL,
+            root TEXT NOT NULL,
+            path TEXT NOT NULL,
+            status TEXT NOT NULL,
+            reason TEXT,
+            bytes INTEGER,
+            tokens INTEGER,
+            language TEXT
+        )",
+        (),
+    )?;
+    let transaction = db.transaction()?;
+    {
+        let mut insert = transaction.prepare(&format!(
+            "INSERT INTO files ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            COLUMNS.join(", ")
+        ))?;
+        for row in rows {
+            // SQLite integers are signed.
+            insert.execute(params![
+                run as i64,
+                root,
+                row.path,
+                row.status,
+                row.reason,
+                row.bytes.map(|bytes| bytes as i64),
+                row.tokens.map(|tokens| tokens as i64),
+                row.language,
+            ])?;
+        }
+    }
+    transaction.commit()?;
+    Ok(())
+}
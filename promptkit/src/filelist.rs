@@ -0,0 +1,37 @@
+use std::fs;
+use std::path::{Component, Path};
+
+use anyhow::{Context, Result, bail};
+
+/// Reads a `--from-filelist` file: one path relative to the root per line, as written by
+/// `--write-filelist`. Blank lines and lines starting with `#` are ignored.
+pub fn read(path: &Path) -> Result<Vec<String>> {
+    let text =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let mut listed = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let relative = Path::new(line)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !relative {
+            bail!(
+                "{}: {line} is not a path inside the root; the list holds paths relative to it",
+                path.display()
+            );
+        }
+        listed.push(line.to_string());
+    }
+    if listed.is_empty() {
+        bail!("{} lists no files", path.display());
+    }
+    Ok(listed)
+}
+
+/// Writes `paths`, one per line.
+pub fn write(path: &Path, paths: &[String]) -> Result<()> {
+    let list: String = paths.iter().map(|path| format!("{path}\n")).collect();
+    fs::write(path, list).with_context(|| format!("failed to write {}", path.display()))
+}
@@ -0,0 +1,123 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+
+const MAX_STATUS_ENTRIES: usize = 100;
+
+/// Renders the `## Git` section: branch, HEAD, recent commits and uncommitted changes.
+pub fn describe(root: &Path, log_count: usize) -> Result<String> {
+    let branch = run(root, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    let head = run(root, &["rev-parse", "HEAD"])?;
+    let log = run(
+        root,
+        &["log", &format!("-{log_count}"), "--format=%h %s (%ar)"],
+    )?;
+    let status = run(root, &["status", "--short", "--untracked-files=normal"])?;
+    let shortstat = run(root, &["diff", "HEAD", "--shortstat"])?;
+
+    let mut section = String::new();
+    writeln!(section, "## Git")?;
+    writeln!(section, "Branch: {}", branch.trim())?;
+    writeln!(section, "HEAD: {}", head.trim())?;
+    writeln!(section)?;
+
+    if log_count > 0 {
+        writeln!(section, "### Recent Commits")?;
+        for line in log.lines() {
+            writeln!(section, "- {}", line)?;
+        }
+        writeln!(section)?;
+    }
+
+    writeln!(section, "### Uncommitted Changes")?;
+    let entries: Vec<&str> = status.lines().collect();
+    if entries.is_empty() {
+        writeln!(section, "Working tree clean.")?;
+    } else {
+        if !shortstat.trim().is_empty() {
+            writeln!(section, "{}", shortstat.trim())?;
+        }
+        let fence = crate::fence_for(&status);
+        writeln!(section, "{}", fence)?;
+        for entry in entries.iter().take(MAX_STATUS_ENTRIES) {
+            writeln!(section, "{}", entry)?;
+        }
+        if entries.len() > MAX_STATUS_ENTRIES {
+            writeln!(section, "... {} more", entries.len() - MAX_STATUS_ENTRIES)?;
+        }
+        writeln!(section, "{}", fence)?;
+    }
+
+    Ok(section)
+}
+
+/// The last commit touching each of `paths` (relative to `root`), as `<hash> (<date>) <subject>`.
+///
+/// Walks the log once, newest first, and stops as soon as every path has been seen. Paths
+/// without commits are left out. Returns `None` if `root` is not in a Git work tree.
+pub fn last_commits(
+    root: &Path,
+    paths: &BTreeSet<&str>,
+) -> Result<Option<HashMap<String, String>>> {
+    if run(root, &["rev-parse", "--is-inside-work-tree"]).is_err() {
+        return Ok(None);
+    }
+    let mut commits = HashMap::new();
+    if paths.is_empty() {
+        return Ok(Some(commits));
+    }
+
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args([
+            "log",
+            "--relative",
+            "--name-only",
+            "--format=%x00%h (%cs) %s",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("failed to run git")?;
+    let stdout = child.stdout.take().context("git log has no stdout")?;
+
+    let mut commit = String::new();
+    for line in BufReader::new(stdout).lines() {
+        let line = line.context("failed to read git log")?;
+        if let Some(header) = line.strip_prefix('\0') {
+            commit = header.to_string();
+        } else if paths.contains(line.as_str()) && !commits.contains_key(&line) {
+            commits.insert(line, commit.clone());
+            if commits.len() == paths.len() {
+                break;
+            }
+        }
+    }
+    // The log may not have been read to the end.
+    let _ = child.kill();
+    let _ = child.wait();
+    Ok(Some(commits))
+}
+
+/// Runs `git -C <root> <args>` and returns stdout, failing on a non-zero exit.
+pub fn run(root: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()
+        .context("failed to run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    String::from_utf8(output.stdout).context("git produced non-UTF-8 output")
+}
@@ -0,0 +1,53 @@
+use regex::Regex;
+
+use crate::with_thousands;
+
+/// Whether any of `patterns` matches somewhere in `contents`.
+pub fn matches(contents: &str, patterns: &[Regex]) -> bool {
+    patterns.iter().any(|pattern| pattern.is_match(contents))
+}
+
+/// The lines with a match of any of `patterns`, each with up to `context` lines around it.
+/// Runs of left-out lines are replaced by a marker saying how many there were.
+pub fn excerpt(contents: &str, patterns: &[Regex], context: usize) -> String {
+    let lines: Vec<&str> = contents.lines().collect();
+    // Byte offset where each line starts, to map matches (which may span lines) to lines.
+    let starts: Vec<usize> = lines
+        .iter()
+        .map(|line| line.as_ptr() as usize - contents.as_ptr() as usize)
+        .collect();
+    let line_of = |offset: usize| starts.partition_point(|&start| start <= offset).max(1) - 1;
+
+    let mut keep = vec![false; lines.len()];
+    for pattern in patterns {
+        for found in pattern.find_iter(contents) {
+            let first = line_of(found.start());
+            let last = line_of(found.end().saturating_sub(1).max(found.start()));
+            let end = (last + context + 1).min(lines.len());
+            keep[first.saturating_sub(context)..end].fill(true);
+        }
+    }
+
+    let mut out = String::new();
+    let mut omitted = 0;
+    for (line, keep) in lines.iter().zip(keep) {
+        if !keep {
+            omitted += 1;
+            continue;
+        }
+        if omitted > 0 {
+            out.push_str(&omitted_marker(omitted));
+            omitted = 0;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    if omitted > 0 {
+        out.push_str(&omitted_marker(omitted));
+    }
+    out
+}
+
+fn omitted_marker(lines: usize) -> String {
+    format!("[... {} lines omitted ...]\n", with_thousands(lines))
+}
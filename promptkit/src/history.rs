@@ -0,0 +1,158 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::FileDump;
+
+/// One recorded `dump` invocation, a line of `<data dir>/promptkit/history.jsonl`.
+#[derive(Serialize, Deserialize)]
+pub struct Entry {
+    pub id: u64,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Working directory the command ran in; relative paths in `args` resolve against it.
+    pub cwd: PathBuf,
+    /// Command line without the program name, e.g. `["dump", "fix the bug", "-p", "src"]`.
+    pub args: Vec<String>,
+    pub files: usize,
+    /// Hash of the included paths and contents, to tell whether a rerun saw the same files.
+    pub files_hash: String,
+    pub tokens: usize,
+}
+
+fn data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("promptkit"))
+}
+
+fn history_path() -> Result<PathBuf> {
+    data_dir()
+        .map(|dir| dir.join("history.jsonl"))
+        .context("failed to determine the data directory")
+}
+
+/// Reads all entries, oldest first. A missing history file is an empty history.
+pub fn load() -> Result<Vec<Entry>> {
+    let path = history_path()?;
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", path.display())),
+    };
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str(line)
+                .with_context(|| format!("invalid history entry in {}", path.display()))
+        })
+        .collect()
+}
+
+pub fn find(id: u64) -> Result<Entry> {
+    match load()?.into_iter().find(|entry| entry.id == id) {
+        Some(entry) => Ok(entry),
+        None => bail!("no history entry with id {id}"),
+    }
+}
+
+/// Appends an entry for a dump of `files` and returns its id.
+pub fn record(args: &[String], files: &[FileDump], tokens: usize) -> Result<u64> {
+    let path = history_path()?;
+    let id = load()?.last().map_or(1, |entry| entry.id + 1);
+    let entry = Entry {
+        id,
+        timestamp: now(),
+        cwd: std::env::current_dir().context("failed to determine current directory")?,
+        args: args.to_vec(),
+        files: files.len(),
+        files_hash: files_hash(files),
+        tokens,
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create directory {}", dir.display()))?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(id)
+}
+
+/// FNV-1a over each file's path and contents, as 16 hex digits. Stable across builds,
+/// unlike `DefaultHasher`.
+pub fn files_hash(files: &[FileDump]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for file in files {
+        for bytes in [file.relative_path.as_bytes(), file.contents.as_bytes()] {
+            for &byte in bytes.iter().chain(&[0]) {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+            }
+        }
+    }
+    format!("{hash:016x}")
+}
+
+/// Renders the last `limit` entries as a table, newest last.
+pub fn render(entries: &[Entry], limit: usize) -> Result<String> {
+    let now = now();
+    let shown = &entries[entries.len().saturating_sub(limit)..];
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{:>5}  {:>9}  {:>8}  {:>6}  command",
+        "id", "when", "tokens", "files"
+    )?;
+    for entry in shown {
+        let command: Vec<String> = entry.args.iter().map(|arg| shell_quote(arg)).collect();
+        writeln!(
+            out,
+            "{:>5}  {:>9}  {:>8}  {:>6}  promptkit {}  (in {})",
+            entry.id,
+            ago(now.saturating_sub(entry.timestamp)),
+            entry.tokens,
+            entry.files,
+            command.join(" "),
+            entry.cwd.display()
+        )?;
+    }
+    Ok(out)
+}
+
+/// Quotes `arg` for display if a shell would otherwise split or expand it.
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_./=:@,+%".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+fn ago(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{secs}s ago"),
+        60..3_600 => format!("{}m ago", secs / 60),
+        3_600..86_400 => format!("{}h ago", secs / 3_600),
+        _ => format!("{}d ago", secs / 86_400),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
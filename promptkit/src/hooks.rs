@@ -0,0 +1,31 @@
+use std::io::{self, Write};
+
+use anyhow::{Context, Result, bail};
+
+use crate::shell;
+
+/// Runs a `[hooks]` command through the shell in the current directory, with `vars` in its
+/// environment and what `input` writes on stdin. Its stdout goes to stderr, so it never mixes
+/// with a prompt written to stdout. Fails if the command does not succeed.
+pub fn run(
+    hook: &str,
+    command: &str,
+    vars: &[(&str, String)],
+    input: impl FnOnce(&mut dyn Write) -> Result<()> + Send,
+) -> Result<()> {
+    let output = shell::run(
+        shell::command(command)
+            .envs(vars.iter().map(|(name, value)| (name, value)))
+            .stdout(io::stderr()),
+        |stdin| {
+            // A hook that does not read its input closes the pipe early; that is fine.
+            let _ = input(stdin);
+        },
+    )
+    .with_context(|| format!("failed to run {hook} hook `{command}`"))?;
+
+    if !output.status.success() {
+        bail!("{hook} hook `{command}` exited with {}", output.status);
+    }
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Detects the fence language of a file.
+///
+/// `overrides` (from the config) are matched by file name, then by extension, before the
+/// built-in tables. Scripts without a known extension fall back to their shebang.
+pub fn detect(
+    relative_path: &str,
+    contents: &str,
+    overrides: &BTreeMap<String, String>,
+) -> Option<String> {
+    let path = Path::new(relative_path);
+    let name = path.file_name()?.to_str()?;
+    let extension = path.extension().and_then(|ext| ext.to_str());
+
+    if let Some(language) = overrides
+        .get(name)
+        .or_else(|| extension.and_then(|ext| overrides.get(ext)))
+    {
+        return Some(language.clone());
+    }
+
+    by_name(name)
+        .or_else(|| extension.and_then(|ext| by_extension(&ext.to_ascii_lowercase())))
+        .or_else(|| by_shebang(contents))
+        .map(str::to_string)
+}
+
+fn by_name(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "Dockerfile" | "Containerfile" => "dockerfile",
+        "Makefile" | "GNUmakefile" | "makefile" => "makefile",
+        "CMakeLists.txt" => "cmake",
+        "Justfile" | "justfile" => "just",
+        "Gemfile" | "Rakefile" => "ruby",
+        "BUILD" | "WORKSPACE" | "BUILD.bazel" => "starlark",
+        ".bashrc" | ".bash_profile" | ".profile" => "bash",
+        ".zshrc" => "zsh",
+        _ => return None,
+    })
+}
+
+fn by_extension(extension: &str) -> Option<&'static str> {
+    Some(match extension {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "jsx",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "tsx",
+        "go" => "go",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "scala" => "scala",
+        "swift" => "swift",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" => "cpp",
+        "cs" => "csharp",
+        "m" | "mm" => "objectivec",
+        "rb" => "ruby",
+        "php" => "php",
+        "pl" | "pm" => "perl",
+        "lua" => "lua",
+        "r" => "r",
+        "jl" => "julia",
+        "dart" => "dart",
+        "ex" | "exs" => "elixir",
+        "erl" | "hrl" => "erlang",
+        "hs" => "haskell",
+        "ml" | "mli" => "ocaml",
+        "clj" | "cljs" | "edn" => "clojure",
+        "zig" => "zig",
+        "nix" => "nix",
+        "sh" | "bash" => "bash",
+        "zsh" => "zsh",
+        "fish" => "fish",
+        "ps1" | "psm1" => "powershell",
+        "bat" | "cmd" => "batch",
+        "sql" => "sql",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "scss" => "scss",
+        "less" => "less",
+        "vue" => "vue",
+        "svelte" => "svelte",
+        "json" | "jsonc" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "xml" | "xsd" | "svg" => "xml",
+        "ini" | "cfg" => "ini",
+        "md" | "markdown" => "markdown",
+        "rst" => "rst",
+        "tex" => "latex",
+        "proto" => "protobuf",
+        "graphql" | "gql" => "graphql",
+        "tf" | "tfvars" | "hcl" => "hcl",
+        "dockerfile" => "dockerfile",
+        "mk" => "makefile",
+        "cmake" => "cmake",
+        "vim" => "vim",
+        "diff" | "patch" => "diff",
+        _ => return None,
+    })
+}
+
+fn by_shebang(contents: &str) -> Option<&'static str> {
+    let line = contents.lines().next()?.strip_prefix("#!")?;
+    let mut words = line.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+    let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+    Some(match program {
+        "python" => "python",
+        "sh" | "bash" | "dash" => "bash",
+        "zsh" => "zsh",
+        "fish" => "fish",
+        "node" | "nodejs" => "javascript",
+        "deno" | "bun" | "ts-node" => "typescript",
+        "ruby" => "ruby",
+        "perl" => "perl",
+        "php" => "php",
+        "lua" => "lua",
+        "Rscript" => "r",
+        "pwsh" => "powershell",
+        _ => return None,
+    })
+}
@@ -0,0 +1,297 @@
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::json;
+use ureq::Agent;
+
+const OPENAI_DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const ANTHROPIC_DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Provider {
+    /// OpenAI Chat Completions, or any compatible server (`OPENAI_API_KEY`, `OPENAI_BASE_URL`).
+    Openai,
+    /// Anthropic Messages API (`ANTHROPIC_API_KEY`, `ANTHROPIC_BASE_URL`).
+    Anthropic,
+}
+
+impl Provider {
+    /// Picks Anthropic for `claude-*` models and the OpenAI-compatible API otherwise.
+    pub fn for_model(model: &str) -> Self {
+        if model.starts_with("claude") {
+            Provider::Anthropic
+        } else {
+            Provider::Openai
+        }
+    }
+
+    fn key_var(self) -> &'static str {
+        match self {
+            Provider::Openai => "OPENAI_API_KEY",
+            Provider::Anthropic => "ANTHROPIC_API_KEY",
+        }
+    }
+
+    fn base_url_var(self) -> &'static str {
+        match self {
+            Provider::Openai => "OPENAI_BASE_URL",
+            Provider::Anthropic => "ANTHROPIC_BASE_URL",
+        }
+    }
+
+    fn default_base_url(self) -> &'static str {
+        match self {
+            Provider::Openai => OPENAI_DEFAULT_BASE_URL,
+            Provider::Anthropic => ANTHROPIC_DEFAULT_BASE_URL,
+        }
+    }
+}
+
+pub struct ChatRequest<'a> {
+    pub provider: Provider,
+    pub model: &'a str,
+    /// Overrides the provider's base URL (and its environment variable).
+    pub base_url: Option<&'a str>,
+    pub prompt: &'a str,
+    pub max_output_tokens: u32,
+    /// Extra attempts after a connection error, 429 or 5xx before any output was streamed.
+    pub retries: u32,
+}
+
+/// Token usage as reported by the API.
+#[derive(Debug, Default)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// Sends the prompt as a single user message and streams the reply text into `out`.
+pub fn send(request: &ChatRequest, out: &mut impl Write) -> Result<Usage> {
+    let provider = request.provider;
+    let api_key = env::var(provider.key_var())
+        .with_context(|| format!("{} is not set", provider.key_var()))?;
+    let base_url = match request.base_url {
+        Some(url) => url.to_string(),
+        None => env::var(provider.base_url_var())
+            .unwrap_or_else(|_| provider.default_base_url().to_string()),
+    };
+    let base_url = base_url.trim_end_matches('/');
+
+    let agent: Agent = Agent::config_builder()
+        .http_status_as_error(false)
+        .build()
+        .into();
+
+    let (url, body) = match provider {
+        Provider::Openai => (
+            format!("{base_url}/chat/completions"),
+            json!({
+                "model": request.model,
+                "messages": [{ "role": "user", "content": request.prompt }],
+                "max_completion_tokens": request.max_output_tokens,
+                "stream": true,
+                "stream_options": { "include_usage": true },
+            }),
+        ),
+        Provider::Anthropic => (
+            format!("{base_url}/v1/messages"),
+            json!({
+                "model": request.model,
+                "messages": [{ "role": "user", "content": request.prompt }],
+                "max_tokens": request.max_output_tokens,
+                "stream": true,
+            }),
+        ),
+    };
+
+    let mut attempt = 0;
+    let response = loop {
+        let mut builder = agent.post(&url).header("content-type", "application/json");
+        builder = match provider {
+            Provider::Openai => builder.header("authorization", format!("Bearer {api_key}")),
+            Provider::Anthropic => builder
+                .header("x-api-key", &api_key)
+                .header("anthropic-version", ANTHROPIC_VERSION),
+        };
+
+        let error = match builder.send(body.to_string()) {
+            Ok(response) if response.status().is_success() => break response,
+            Ok(response) => {
+                let status = response.status();
+                let message = response
+                    .into_body()
+                    .read_to_string()
+                    .unwrap_or_else(|err| err.to_string());
+                let retryable = status.as_u16() == 429 || status.is_server_error();
+                if !retryable || attempt >= request.retries {
+                    bail!("{} returned {}: {}", url, status, message.trim());
+                }
+                format!("{status}")
+            }
+            Err(err) => {
+                if attempt >= request.retries {
+                    return Err(err).with_context(|| format!("request to {url} failed"));
+                }
+                err.to_string()
+            }
+        };
+
+        let delay = Duration::from_secs(1 << attempt);
+        attempt += 1;
+        eprintln!(
+            "Retrying: attempt={}/{}, error={}, delay={}s",
+            attempt,
+            request.retries,
+            error,
+            delay.as_secs()
+        );
+        thread::sleep(delay);
+    };
+
+    let reader = BufReader::new(response.into_body().into_reader());
+    match provider {
+        Provider::Openai => stream_openai(reader, out),
+        Provider::Anthropic => stream_anthropic(reader, out),
+    }
+}
+
+/// Yields the payload of each server-sent event `data:` line.
+fn sse_data(reader: impl BufRead) -> impl Iterator<Item = Result<String>> {
+    reader.lines().filter_map(|line| match line {
+        Ok(line) => line
+            .strip_prefix("data:")
+            .map(|data| Ok(data.trim().to_string())),
+        Err(err) => Some(Err(err).context("failed to read response stream")),
+    })
+}
+
+#[derive(Deserialize)]
+struct OpenAiChunk {
+    #[serde(default)]
+    choices: Vec<OpenAiChoice>,
+    usage: Option<OpenAiUsage>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiChoice {
+    delta: OpenAiDelta,
+}
+
+#[derive(Deserialize)]
+struct OpenAiDelta {
+    content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OpenAiUsage {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+}
+
+fn stream_openai(reader: impl BufRead, out: &mut impl Write) -> Result<Usage> {
+    let mut usage = Usage::default();
+    for data in sse_data(reader) {
+        let data = data?;
+        if data == "[DONE]" {
+            break;
+        }
+        let chunk: OpenAiChunk = serde_json::from_str(&data)
+            .with_context(|| format!("unexpected stream chunk: {data}"))?;
+        for choice in chunk.choices {
+            if let Some(content) = choice.delta.content {
+                out.write_all(content.as_bytes())?;
+                out.flush()?;
+            }
+        }
+        if let Some(chunk_usage) = chunk.usage {
+            usage.input_tokens = chunk_usage.prompt_tokens;
+            usage.output_tokens = chunk_usage.completion_tokens;
+        }
+    }
+    Ok(usage)
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicEvent {
+    MessageStart {
+        message: AnthropicMessage,
+    },
+    ContentBlockDelta {
+        delta: AnthropicDelta,
+    },
+    MessageDelta {
+        usage: AnthropicOutputUsage,
+    },
+    MessageStop,
+    Error {
+        error: AnthropicError,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct AnthropicMessage {
+    usage: AnthropicUsage,
+}
+
+#[derive(Deserialize)]
+struct AnthropicUsage {
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+#[derive(Deserialize)]
+struct AnthropicOutputUsage {
+    output_tokens: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicDelta {
+    TextDelta {
+        text: String,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct AnthropicError {
+    message: String,
+}
+
+fn stream_anthropic(reader: impl BufRead, out: &mut impl Write) -> Result<Usage> {
+    let mut usage = Usage::default();
+    for data in sse_data(reader) {
+        let data = data?;
+        let event: AnthropicEvent = serde_json::from_str(&data)
+            .with_context(|| format!("unexpected stream event: {data}"))?;
+        match event {
+            AnthropicEvent::MessageStart { message } => {
+                usage.input_tokens = message.usage.input_tokens;
+                usage.output_tokens = message.usage.output_tokens;
+            }
+            AnthropicEvent::ContentBlockDelta {
+                delta: AnthropicDelta::TextDelta { text },
+            } => {
+                out.write_all(text.as_bytes())?;
+                out.flush()?;
+            }
+            AnthropicEvent::MessageDelta { usage: delta } => {
+                usage.output_tokens = delta.output_tokens;
+            }
+            AnthropicEvent::MessageStop => break,
+            AnthropicEvent::Error { error } => bail!("stream error: {}", error.message),
+            AnthropicEvent::ContentBlockDelta { .. } | AnthropicEvent::Other => {}
+        }
+    }
+    Ok(usage)
+}
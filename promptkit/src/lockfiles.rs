@@ -0,0 +1,546 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::with_thousands;
+
+/// Direct dependencies listed by name in a lockfile summary; the rest are only counted.
+const MAX_LISTED: usize = 50;
+/// JavaScript and CSS files at least this large with lines this long on average are minified.
+const MIN_MINIFIED_BYTES: usize = 2048;
+const MINIFIED_LINE_LENGTH: usize = 300;
+/// Directories whose JavaScript and CSS files are third-party copies.
+const VENDOR_DIRS: &[&str] = &["vendor", "vendors", "third_party", "third-party"];
+/// Characters of an asset's leading comment kept in its summary; it often names the library.
+const MAX_BANNER: usize = 160;
+
+/// What kind of file was replaced by a summary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Lockfile,
+    Minified,
+    Vendored,
+}
+
+impl Kind {
+    pub fn label(self) -> &'static str {
+        match self {
+            Kind::Lockfile => "lockfile",
+            Kind::Minified => "minified",
+            Kind::Vendored => "vendored",
+        }
+    }
+
+    /// `a lockfile`, `a minified asset`, ...
+    pub fn description(self) -> &'static str {
+        match self {
+            Kind::Lockfile => "a lockfile",
+            Kind::Minified => "a minified asset",
+            Kind::Vendored => "a vendored asset",
+        }
+    }
+}
+
+/// A short summary to include instead of a lockfile or a minified or vendored asset, or `None`
+/// for other files. `dir` is the file's directory on disk, where the manifest naming direct
+/// dependencies is looked for.
+pub fn condense(dir: &Path, relative_path: &str, contents: &str) -> Option<(Kind, String)> {
+    let path = Path::new(relative_path);
+    let name = path.file_name()?.to_str()?;
+    if let Some(lock) = lockfile(dir, name, contents) {
+        return Some((Kind::Lockfile, lock.render()));
+    }
+
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    let script_or_style = matches!(extension, "js" | "mjs" | "cjs" | "css");
+    let lines = contents.lines().count().max(1);
+    let kind = if extension == "map"
+        || (script_or_style
+            && (name.contains(".min.")
+                || (contents.len() >= MIN_MINIFIED_BYTES
+                    && contents.len() / lines > MINIFIED_LINE_LENGTH)))
+    {
+        Kind::Minified
+    } else if script_or_style
+        && path.parent().is_some_and(|parent| {
+            parent
+                .iter()
+                .any(|dir| VENDOR_DIRS.iter().any(|vendor| dir == *vendor))
+        })
+    {
+        Kind::Vendored
+    } else {
+        return None;
+    };
+
+    let mut summary = format!("Lines: {}\n", with_thousands(lines));
+    let first = contents.trim_start().lines().next().unwrap_or("");
+    if first.starts_with("/*") || first.starts_with("//") {
+        let banner: String = first.chars().take(MAX_BANNER).collect();
+        summary.push_str(&format!("Banner: {banner}\n"));
+    }
+    Some((kind, summary))
+}
+
+/// Package counts and direct dependencies read from a lockfile. Either may be unknown when
+/// the file or its manifest could not be parsed.
+#[derive(Default)]
+struct Lock {
+    packages: Option<usize>,
+    /// Direct dependencies by name, with their locked version when known.
+    direct: Option<BTreeMap<String, Option<String>>>,
+}
+
+impl Lock {
+    fn render(&self) -> String {
+        let mut out = String::new();
+        if let Some(count) = self.packages {
+            out.push_str(&format!("Locked packages: {}\n", with_thousands(count)));
+        }
+        if let Some(direct) = &self.direct {
+            let mut listed: Vec<String> = direct
+                .iter()
+                .take(MAX_LISTED)
+                .map(|(name, version)| match version {
+                    Some(version) => format!("{name} {version}"),
+                    None => name.clone(),
+                })
+                .collect();
+            if direct.len() > MAX_LISTED {
+                listed.push(format!("and {} more", direct.len() - MAX_LISTED));
+            }
+            out.push_str(&format!(
+                "Direct dependencies ({}): {}\n",
+                direct.len(),
+                listed.join(", ")
+            ));
+        }
+        if out.is_empty() {
+            out.push_str("[Could not be parsed]\n");
+        }
+        out
+    }
+}
+
+fn lockfile(dir: &Path, name: &str, contents: &str) -> Option<Lock> {
+    let lock = match name {
+        "Cargo.lock" => cargo(contents),
+        "package-lock.json" | "npm-shrinkwrap.json" => npm(dir, contents),
+        "yarn.lock" => Lock {
+            packages: Some(
+                contents
+                    .lines()
+                    .filter(|line| {
+                        line.ends_with(':')
+                            && !line.starts_with([' ', '#'])
+                            && *line != "__metadata:"
+                    })
+                    .count(),
+            ),
+            direct: package_json(dir),
+        },
+        "pnpm-lock.yaml" => Lock {
+            packages: Some(section_entries(contents, "packages:", "  ")),
+            direct: package_json(dir),
+        },
+        "bun.lock" => Lock {
+            packages: Some(section_entries(contents, "  \"packages\": {", "    \"")),
+            direct: package_json(dir),
+        },
+        "poetry.lock" => poetry(dir, contents),
+        "uv.lock" => uv(contents),
+        "Pipfile.lock" => pipfile(contents),
+        "go.sum" => go(dir, contents),
+        "Gemfile.lock" => gemfile(contents),
+        "composer.lock" => composer(dir, contents),
+        _ => return None,
+    };
+    Some(lock)
+}
+
+/// Lines indented by exactly `indent` under the line `header`, up to the next unindented line.
+fn section_entries(contents: &str, header: &str, indent: &str) -> usize {
+    contents
+        .lines()
+        .skip_while(|line| *line != header)
+        .skip(1)
+        .take_while(|line| line.is_empty() || line.starts_with(' '))
+        .filter(|line| {
+            line.strip_prefix(indent)
+                .is_some_and(|rest| !rest.starts_with(' '))
+        })
+        .count()
+}
+
+fn cargo(contents: &str) -> Lock {
+    let Ok(table) = toml::from_str::<toml::Table>(contents) else {
+        return Lock::default();
+    };
+    let packages: Vec<&toml::Table> = table
+        .get("package")
+        .and_then(|packages| packages.as_array())
+        .map(|packages| packages.iter().filter_map(|p| p.as_table()).collect())
+        .unwrap_or_default();
+    let mut versions: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for package in &packages {
+        if let (Some(name), Some(version)) =
+            (str_field(package, "name"), str_field(package, "version"))
+        {
+            versions.entry(name).or_default().insert(version);
+        }
+    }
+    // Workspace members have no `source`; their dependencies are the direct ones.
+    let members: Vec<&toml::Table> = packages
+        .iter()
+        .copied()
+        .filter(|package| !package.contains_key("source"))
+        .collect();
+    let member_names: BTreeSet<&str> = members
+        .iter()
+        .filter_map(|package| str_field(package, "name"))
+        .collect();
+
+    let mut direct = BTreeMap::new();
+    for member in &members {
+        let dependencies = member
+            .get("dependencies")
+            .and_then(|deps| deps.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|dep| dep.as_str());
+        for dependency in dependencies {
+            // `name`, or `name version` when several versions are locked.
+            let mut words = dependency.split_whitespace();
+            let Some(name) = words.next() else {
+                continue;
+            };
+            if member_names.contains(name) {
+                continue;
+            }
+            let version = words.next().map(str::to_string).or_else(|| {
+                let locked = versions.get(name)?;
+                (locked.len() == 1).then(|| locked.iter().next().map(|v| v.to_string()))?
+            });
+            direct.insert(name.to_string(), version);
+        }
+    }
+    Lock {
+        packages: Some(packages.len() - members.len()),
+        direct: Some(direct),
+    }
+}
+
+fn npm(dir: &Path, contents: &str) -> Lock {
+    let Ok(json) = serde_json::from_str::<Value>(contents) else {
+        return Lock::default();
+    };
+    // Lockfile v2 and v3 list every package under `packages`, keyed by install path.
+    if let Some(packages) = json.get("packages").and_then(Value::as_object) {
+        let root = packages.get("").cloned().unwrap_or(Value::Null);
+        let mut direct = BTreeMap::new();
+        for field in ["dependencies", "devDependencies", "optionalDependencies"] {
+            for name in root
+                .get(field)
+                .and_then(Value::as_object)
+                .into_iter()
+                .flatten()
+                .map(|(name, _)| name)
+            {
+                let version = packages
+                    .get(&format!("node_modules/{name}"))
+                    .and_then(|package| package.get("version"))
+                    .and_then(Value::as_str)
+                    .map(str::to_string);
+                direct.insert(name.clone(), version);
+            }
+        }
+        return Lock {
+            packages: Some(
+                packages
+                    .keys()
+                    .filter(|key| key.contains("node_modules/"))
+                    .count(),
+            ),
+            direct: Some(direct),
+        };
+    }
+    Lock {
+        packages: json
+            .get("dependencies")
+            .and_then(Value::as_object)
+            .map(|deps| deps.len()),
+        direct: package_json(dir),
+    }
+}
+
+/// Dependencies declared in `package.json`, without versions.
+fn package_json(dir: &Path) -> Option<BTreeMap<String, Option<String>>> {
+    let json: Value =
+        serde_json::from_str(&fs::read_to_string(dir.join("package.json")).ok()?).ok()?;
+    Some(json_keys(
+        &json,
+        &["dependencies", "devDependencies", "optionalDependencies"],
+    ))
+}
+
+fn json_keys(json: &Value, fields: &[&str]) -> BTreeMap<String, Option<String>> {
+    fields
+        .iter()
+        .filter_map(|field| json.get(field).and_then(Value::as_object))
+        .flatten()
+        .map(|(name, _)| (name.clone(), None))
+        .collect()
+}
+
+fn poetry(dir: &Path, contents: &str) -> Lock {
+    let Ok(table) = toml::from_str::<toml::Table>(contents) else {
+        return Lock::default();
+    };
+    let versions = toml_versions(&table);
+    let direct = fs::read_to_string(dir.join("pyproject.toml"))
+        .ok()
+        .and_then(|text| toml::from_str::<toml::Table>(&text).ok())
+        .map(|pyproject| {
+            let mut names: Vec<String> = Vec::new();
+            // PEP 621 `[project]` dependencies are requirement strings like `requests>=2`.
+            let requirements = pyproject
+                .get("project")
+                .and_then(|project| project.get("dependencies"))
+                .and_then(|deps| deps.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|dep| dep.as_str());
+            names.extend(requirements.map(requirement_name));
+            let poetry = pyproject
+                .get("tool")
+                .and_then(|tool| tool.get("poetry"))
+                .and_then(|poetry| poetry.get("dependencies"))
+                .and_then(|deps| deps.as_table());
+            names.extend(poetry.into_iter().flatten().map(|(name, _)| name.clone()));
+            names
+                .into_iter()
+                .filter(|name| name != "python")
+                .map(|name| {
+                    let version = versions.get(&normalize(&name)).cloned();
+                    (name, version)
+                })
+                .collect()
+        });
+    Lock {
+        packages: Some(versions.len()),
+        direct,
+    }
+}
+
+fn uv(contents: &str) -> Lock {
+    let Ok(table) = toml::from_str::<toml::Table>(contents) else {
+        return Lock::default();
+    };
+    let versions = toml_versions(&table);
+    let packages: Vec<&toml::Table> = table
+        .get("package")
+        .and_then(|packages| packages.as_array())
+        .map(|packages| packages.iter().filter_map(|p| p.as_table()).collect())
+        .unwrap_or_default();
+    // The project itself is locked as an editable or virtual package at `.`.
+    let is_root = |package: &&toml::Table| {
+        package
+            .get("source")
+            .and_then(|source| source.as_table())
+            .is_some_and(|source| {
+                ["editable", "virtual"]
+                    .iter()
+                    .any(|key| source.get(*key).and_then(|dir| dir.as_str()) == Some("."))
+            })
+    };
+    let mut roots = 0;
+    let mut direct = BTreeMap::new();
+    for root in packages.iter().copied().filter(is_root) {
+        roots += 1;
+        let groups = root
+            .get("dev-dependencies")
+            .and_then(|groups| groups.as_table())
+            .into_iter()
+            .flat_map(|groups| groups.values());
+        let lists = root.get("dependencies").into_iter().chain(groups);
+        for dependency in lists.filter_map(|list| list.as_array()).flatten() {
+            if let Some(name) = dependency.get("name").and_then(|name| name.as_str()) {
+                direct.insert(name.to_string(), versions.get(&normalize(name)).cloned());
+            }
+        }
+    }
+    Lock {
+        packages: Some(packages.len() - roots),
+        direct: Some(direct),
+    }
+}
+
+/// Locked version by normalized name, from `[[package]]` tables with `name` and `version`.
+fn toml_versions(table: &toml::Table) -> BTreeMap<String, String> {
+    table
+        .get("package")
+        .and_then(|packages| packages.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|package| {
+            let package = package.as_table()?;
+            Some((
+                normalize(str_field(package, "name")?),
+                str_field(package, "version")?.to_string(),
+            ))
+        })
+        .collect()
+}
+
+fn pipfile(contents: &str) -> Lock {
+    let Ok(json) = serde_json::from_str::<Value>(contents) else {
+        return Lock::default();
+    };
+    let count = ["default", "develop"]
+        .iter()
+        .filter_map(|section| json.get(section).and_then(Value::as_object))
+        .map(|packages| packages.len())
+        .sum();
+    Lock {
+        packages: Some(count),
+        direct: None,
+    }
+}
+
+fn go(dir: &Path, contents: &str) -> Lock {
+    let modules: BTreeSet<&str> = contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let module = fields.next()?;
+            let version = fields.next()?;
+            (!version.ends_with("/go.mod")).then_some(module)
+        })
+        .collect();
+    let direct = fs::read_to_string(dir.join("go.mod")).ok().map(|go_mod| {
+        let mut direct = BTreeMap::new();
+        let mut in_block = false;
+        for line in go_mod.lines().map(str::trim) {
+            let requirement = if in_block {
+                if line == ")" {
+                    in_block = false;
+                    continue;
+                }
+                line
+            } else if line == "require (" {
+                in_block = true;
+                continue;
+            } else if let Some(rest) = line.strip_prefix("require ") {
+                rest
+            } else {
+                continue;
+            };
+            if requirement.contains("// indirect") {
+                continue;
+            }
+            let mut fields = requirement.split_whitespace();
+            if let (Some(module), Some(version)) = (fields.next(), fields.next()) {
+                direct.insert(module.to_string(), Some(version.to_string()));
+            }
+        }
+        direct
+    });
+    Lock {
+        packages: Some(modules.len()),
+        direct,
+    }
+}
+
+fn gemfile(contents: &str) -> Lock {
+    // `    name (version)` lines under `specs:`, and `  name` lines under `DEPENDENCIES`.
+    let mut versions = BTreeMap::new();
+    let mut direct = BTreeMap::new();
+    let mut section = "";
+    for line in contents.lines() {
+        if !line.starts_with(' ') {
+            section = line.trim();
+            continue;
+        }
+        let entry = line.trim_start();
+        let indent = line.len() - entry.len();
+        let name = entry
+            .split_whitespace()
+            .next()
+            .unwrap_or("")
+            .trim_end_matches('!');
+        if section == "DEPENDENCIES" && indent == 2 {
+            direct.insert(name.to_string(), None);
+        } else if indent == 4
+            && let Some(version) = entry
+                .split_once('(')
+                .and_then(|(_, rest)| rest.strip_suffix(')'))
+        {
+            versions.insert(name.to_string(), version.to_string());
+        }
+    }
+    for (name, version) in direct.iter_mut() {
+        *version = versions.get(name).cloned();
+    }
+    Lock {
+        packages: Some(versions.len()),
+        direct: Some(direct),
+    }
+}
+
+fn composer(dir: &Path, contents: &str) -> Lock {
+    let Ok(json) = serde_json::from_str::<Value>(contents) else {
+        return Lock::default();
+    };
+    let packages: Vec<&Value> = ["packages", "packages-dev"]
+        .iter()
+        .filter_map(|section| json.get(section).and_then(Value::as_array))
+        .flatten()
+        .collect();
+    let versions: BTreeMap<&str, &str> = packages
+        .iter()
+        .filter_map(|package| {
+            Some((
+                package.get("name")?.as_str()?,
+                package.get("version")?.as_str()?,
+            ))
+        })
+        .collect();
+    let direct = fs::read_to_string(dir.join("composer.json"))
+        .ok()
+        .and_then(|text| serde_json::from_str::<Value>(&text).ok())
+        .map(|manifest| {
+            json_keys(&manifest, &["require", "require-dev"])
+                .into_keys()
+                // Platform requirements are not packages.
+                .filter(|name| name != "php" && !name.starts_with("ext-"))
+                .map(|name| {
+                    let version = versions.get(name.as_str()).map(|v| v.to_string());
+                    (name, version)
+                })
+                .collect()
+        });
+    Lock {
+        packages: Some(packages.len()),
+        direct,
+    }
+}
+
+fn str_field<'a>(table: &'a toml::Table, key: &str) -> Option<&'a str> {
+    table.get(key).and_then(|value| value.as_str())
+}
+
+/// The distribution name of a PEP 508 requirement such as `requests[socks]>=2.31`.
+fn requirement_name(requirement: &str) -> String {
+    requirement
+        .split(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .next()
+        .unwrap_or("")
+        .to_string()
+}
+
+/// Python package names compare case-insensitively, with `-`, `_` and `.` equivalent.
+fn normalize(name: &str) -> String {
+    name.to_ascii_lowercase().replace(['_', '.'], "-")
+}
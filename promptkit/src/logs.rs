@@ -0,0 +1,138 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+/// Bytes read at a time when scanning a file backwards for `--tail`.
+const CHUNK: u64 = 64 * 1024;
+
+/// Which lines of a log file to keep.
+#[derive(Clone, Copy, Debug)]
+pub enum Lines {
+    Head(usize),
+    Tail(usize),
+}
+
+/// The `--head`/`--tail` mode and the `--log-glob` patterns it applies to.
+pub struct LogFilter {
+    globs: Gitignore,
+    lines: Option<Lines>,
+}
+
+impl LogFilter {
+    pub fn new(root: &Path, globs: &[String], lines: Option<Lines>) -> Result<Self> {
+        let mut builder = GitignoreBuilder::new(root);
+        for glob in globs {
+            builder
+                .add_line(None, glob)
+                .with_context(|| format!("invalid glob {glob}"))?;
+        }
+        Ok(LogFilter {
+            globs: builder.build()?,
+            lines,
+        })
+    }
+
+    /// The lines to keep of a file, if it is a log and `--head` or `--tail` was given.
+    pub fn lines_for(&self, relative_path: &str) -> Option<Lines> {
+        self.lines.filter(|_| {
+            self.globs
+                .matched_path_or_any_parents(relative_path, false)
+                .is_ignore()
+        })
+    }
+}
+
+/// Reads the first or last lines of a file without reading the rest, and says whether
+/// anything was left out.
+pub fn excerpt(path: &Path, lines: Lines) -> io::Result<(Vec<u8>, bool)> {
+    let mut file = File::open(path)?;
+    match lines {
+        Lines::Head(count) => head(file, count),
+        Lines::Tail(count) => {
+            let len = file.metadata()?.len();
+            tail(&mut file, len, count)
+        }
+    }
+}
+
+/// [`excerpt`] of a stream that can only be read through once, such as an archive entry.
+/// At most `max_bytes` of the excerpt are kept, enough to tell it is over the size limit.
+pub fn excerpt_from(mut reader: impl Read, lines: Lines, max_bytes: u64) -> io::Result<Vec<u8>> {
+    match lines {
+        Lines::Head(count) => head(reader.take(max_bytes), count).map(|(data, _)| data),
+        Lines::Tail(0) => Ok(Vec::new()),
+        Lines::Tail(count) => {
+            let mut data = Vec::new();
+            let mut block = vec![0; CHUNK as usize];
+            loop {
+                let read = match reader.read(&mut block) {
+                    Ok(0) => break,
+                    Ok(read) => read,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => return Err(err),
+                };
+                data.extend_from_slice(&block[..read]);
+                if block[..read].contains(&b'\n')
+                    && let Some(idx) = last_lines(&data, count)
+                {
+                    data.drain(..idx);
+                }
+                let over = data.len().saturating_sub(max_bytes as usize);
+                data.drain(..over);
+            }
+            Ok(data)
+        }
+    }
+}
+
+fn head(reader: impl Read, count: usize) -> io::Result<(Vec<u8>, bool)> {
+    let mut reader = BufReader::new(reader);
+    let mut data = Vec::new();
+    for _ in 0..count {
+        if reader.read_until(b'\n', &mut data)? == 0 {
+            break;
+        }
+    }
+    let more = !reader.fill_buf()?.is_empty();
+    Ok((data, more))
+}
+
+fn tail(file: &mut File, len: u64, count: usize) -> io::Result<(Vec<u8>, bool)> {
+    if count == 0 {
+        return Ok((Vec::new(), len > 0));
+    }
+    let mut start = len;
+    let mut data = Vec::new();
+    // A final newline ends the last line rather than starting another one.
+    let mut newlines = 0;
+    while start > 0 && newlines <= count {
+        let size = CHUNK.min(start);
+        start -= size;
+        file.seek(SeekFrom::Start(start))?;
+        let mut block = vec![0; size as usize];
+        file.read_exact(&mut block)?;
+        newlines += block.iter().filter(|&&byte| byte == b'\n').count();
+        block.extend_from_slice(&data);
+        data = block;
+    }
+
+    match last_lines(&data, count) {
+        Some(idx) => Ok((data[idx..].to_vec(), true)),
+        None => Ok((data, start > 0)),
+    }
+}
+
+/// Where the last `count` lines of `data` start, if there are more lines than that.
+fn last_lines(data: &[u8], count: usize) -> Option<usize> {
+    // A final newline ends the last line rather than starting another one.
+    let body = data.strip_suffix(b"\n").unwrap_or(data);
+    body.iter()
+        .enumerate()
+        .rev()
+        .filter(|&(_, &byte)| byte == b'\n')
+        .nth(count - 1)
+        .map(|(idx, _)| idx + 1)
+}
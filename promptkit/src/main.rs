@@ -1,18 +1,76 @@
-use std::collections::BTreeMap;
+use std::collections::HashMap;
 use std::env;
 use std::fmt;
 use std::fmt::Write as _;
 use std::fs;
-use std::io::{self, Write};
-use std::path::{Path, PathBuf};
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::path::{MAIN_SEPARATOR, MAIN_SEPARATOR_STR, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use anyhow::{Context, Result};
-use clap::{Args, Parser, Subcommand};
+mod anonymize;
+mod archive;
+mod cache;
+mod chunk;
+mod compare;
+mod completions;
+mod config;
+mod decode;
+mod deps;
+mod diffsplit;
+mod environment;
+mod export;
+mod filelist;
+mod git;
+mod grep;
+mod history;
+mod hooks;
+mod lang;
+mod llm;
+mod lockfiles;
+mod logs;
+mod manifest;
+mod mcp;
+mod notebook;
+mod preprocess;
+mod presets;
+mod pricing;
+mod progress;
+mod rank;
+mod recency;
+mod redact;
+mod remote;
+mod response;
+mod shell;
+mod snippets;
+mod stats;
+mod stream;
+mod strip;
+mod summarize;
+mod summary;
+mod template;
+mod tree;
+mod watch;
+mod workspace;
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use ignore::{DirEntry, WalkBuilder};
-use tiktoken_rs::o200k_base;
+use rayon::prelude::*;
+use regex::Regex;
+use tiktoken_rs::{CoreBPE, o200k_base};
+
+use crate::config::Config;
+use crate::logs::LogFilter;
+use crate::preprocess::Preprocessors;
+use crate::recency::Recency;
 
 const TOKENIZER_NAME: &str = "o200k_base";
 
+/// Number of most expensive files listed in the dump stats.
+const TOP_FILES: usize = 10;
+
 const DEFAULT_IGNORED_DIRS: [&str; 5] = [".git", "node_modules", "target", ".venv", "venv"];
 
 #[derive(Parser, Debug)]
@@ -20,9 +78,20 @@ const DEFAULT_IGNORED_DIRS: [&str; 5] = [".git", "node_modules", "target", ".ven
     name = "prompkit",
     version,
     about = "Handy tools for working with prompts",
-    propagate_version = true
+    propagate_version = true,
+    // A profile's flags come before the user's, so the user's win.
+    args_override_self = true
 )]
 struct Cli {
+    /// Config file to use instead of `<config dir>/promptkit/config.toml`.
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Do not read or write the cache of file token counts (`<cache dir>/promptkit`).
+    #[arg(long, global = true)]
+    no_cache: bool,
+    /// Do not show progress on stderr. Progress is only shown when stderr is a terminal.
+    #[arg(short, long, global = true)]
+    quiet: bool,
     #[command(subcommand)]
     command: Command,
 }
@@ -31,24 +100,558 @@ struct Cli {
 enum Command {
     /// Dump repository context and file contents as a prompt.
     ///
-    /// Output is written to stdout, so you can pipe it to a CLI agent.
+    /// Output is written to stdout (or `--output`), so you can pipe it to a CLI agent.
     /// Some stats info is written to stderr.
     /// Some common ignore dirs e.g., `node_modules`, `target` will be ignored, and `.gitignore` will also be respected
+    /// Detected secrets are replaced with `[REDACTED:<kind>]` unless `--no-redact` is given.
     Dump(DumpArgs),
+    /// Dump repository context like `dump`, then send it to an LLM API and stream the reply.
+    ///
+    /// Requires `--model`, e.g. `gpt-4o` or `claude-sonnet-4-5`.
+    /// The API key is read from `OPENAI_API_KEY` or `ANTHROPIC_API_KEY`.
+    /// Stats and token usage are written to stderr.
+    #[command(alias = "run")]
+    Chat(ChatArgs),
     /// Count tokens in one or more files using the o200k_base tokenizer.
     Count(CountArgs),
+    /// Split a unified diff into per-file (or token-bounded) review prompts.
+    ///
+    /// Each part is self-contained: a preamble, the files it covers, their diffs and the task.
+    Diffsplit(DiffsplitArgs),
+    /// List recorded dumps, newest last.
+    ///
+    /// Dumps are recorded when `record_history = true` is set in the config.
+    History(HistoryArgs),
+    /// Run a recorded dump again with the same arguments, from the same directory.
+    Rerun(RerunArgs),
+    /// Run a Model Context Protocol server over stdio.
+    ///
+    /// Exposes `dump_context`, `get_file` and `repo_stats` tools scoped to PATH, so agents can
+    /// fetch context on demand.
+    Mcp(McpArgs),
+    /// Show a per-directory and per-extension breakdown of files, bytes and tokens.
+    ///
+    /// Files are collected with the same rules as `dump`, but no prompt is emitted.
+    Stats(StatsArgs),
+    /// Print the file tree that `dump` would include, annotated with token and byte counts.
+    Tree(TreeArgs),
+    /// Fill a prompt template with variables and file contents.
+    ///
+    /// `{{name}}` is replaced by the value of `--var name=...`, `{{file:PATH}}` by the contents
+    /// of PATH and `{{files}}` by every `--file`, fenced and headed like `dump` does.
+    /// Detected secrets in embedded files are redacted unless `--no-redact` is given.
+    Render(RenderArgs),
+    /// Save, list and print reusable prompt fragments, like coding guidelines or review
+    /// checklists.
+    ///
+    /// Snippets are stored in `<config dir>/promptkit/snippets` and inserted before the task
+    /// of a dump with `--snippet NAME`.
+    Snippet(SnippetArgs),
+    /// Print a shell completion script.
+    ///
+    /// For example `promptkit completions bash > ~/.local/share/bash-completion/completions/promptkit`,
+    /// or `promptkit completions zsh > "${fpath[1]}/_promptkit"`.
+    Completions(CompletionsArgs),
+    /// Print a man page covering every command, in roff format.
+    ///
+    /// For example `promptkit man > ~/.local/share/man/man1/promptkit.1`.
+    Man,
 }
 
 #[derive(Args, Debug)]
 struct DumpArgs {
     /// Message describing what you want the AI to do with the context.
-    task: String,
-    /// Directory to dump. Defaults to the current working directory.
+    #[arg(required_unless_present_any = ["task_fragments", "task_file"])]
+    task: Option<String>,
+    /// Directories and files to dump, after TASK. Defaults to the current working directory.
+    ///
+    /// With more than one, or a single file, paths in the dump are relative to the closest
+    /// directory containing all of them.
+    #[arg(value_name = "PATH")]
+    paths: Vec<PathBuf>,
+    /// Additional task text, appended after TASK. Can be repeated.
+    #[arg(long = "task", value_name = "TEXT")]
+    task_fragments: Vec<String>,
+    /// Read task text from FILE ('-' for stdin), appended after any `--task`. Can be repeated.
+    #[arg(long, value_name = "FILE")]
+    task_file: Vec<PathBuf>,
+    /// Directory or file to dump, like PATH. Can be repeated.
     #[arg(short, long, value_name = "PATH")]
+    path: Vec<PathBuf>,
+    /// Apply the flags of a profile defined under `[profiles]` in the config.
+    ///
+    /// Flags given on the command line override the profile's.
+    #[arg(short = 'P', long, value_name = "NAME")]
+    profile: Option<String>,
+    /// Dump a remote Git repository instead, e.g. `https://github.com/org/name@v1.0`.
+    ///
+    /// The repository is shallow-fetched into a temporary directory that is removed afterwards.
+    /// The optional `@ref` may be a branch, tag or commit SHA.
+    #[arg(long, value_name = "URL[@REF]", conflicts_with_all = ["path", "paths"])]
+    repo: Option<String>,
+    /// Dump the contents of a `.tar`, `.tar.gz`/`.tgz` or `.zip` archive instead.
+    ///
+    /// Entries are read straight from the archive and go through the same ignore, size and
+    /// encoding rules, with sizes checked as the archive declares them before anything is
+    /// decompressed. Links are left out.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["path", "paths", "repo"])]
+    archive: Option<PathBuf>,
+    /// Dump the differences between two versions of a directory instead: unified diffs of
+    /// changed files, the contents of added and removed ones, and a file tree marking each.
+    ///
+    /// Both directories go through the same collection rules; files that are the same in
+    /// both are only listed in the tree.
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["OLD", "NEW"],
+        conflicts_with_all = [
+            "path", "paths", "repo", "archive", "watch", "anonymize", "file_info",
+            "workspace_member", "around",
+        ]
+    )]
+    compare: Option<Vec<PathBuf>>,
+    #[command(flatten)]
+    collect: CollectArgs,
+    /// Model the prompt is meant for; used to estimate input cost in the stats.
+    #[arg(long, env = "PROMPTKIT_MODEL")]
+    model: Option<String>,
+    /// Append instructions on how the model should format its answer: unified diffs, full
+    /// files under path headings, a JSON object, or prose.
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    response_format: Option<response::ResponseFormat>,
+    /// Insert a snippet saved with `promptkit snippet add` before the task. Can be repeated.
+    #[arg(long, value_name = "NAME")]
+    snippet: Vec<String>,
+    /// Order of files in the dump. Under `--max-tokens`, files are dropped from the end.
+    #[arg(long, value_enum, default_value_t = rank::Order::Path)]
+    order: rank::Order,
+    /// Token budget for the whole prompt; files that do not fit are skipped.
+    #[arg(long, value_name = "N")]
+    max_tokens: Option<usize>,
+    /// Write the prompt to FILE instead of stdout (with `chat`, a copy of the prompt sent).
+    #[arg(short, long, value_name = "FILE", conflicts_with = "chunk_dir")]
+    output: Option<PathBuf>,
+    /// Write a JSON manifest of included and skipped files (with sizes and token counts) to FILE.
+    #[arg(long, value_name = "FILE")]
+    manifest: Option<PathBuf>,
+    /// Write the paths of the included files to FILE, sorted and one per line relative to the
+    /// root, to dump the same selection again with `--from-filelist`. With `--anonymize`, the
+    /// list holds the real paths.
+    #[arg(long, value_name = "FILE")]
+    write_filelist: Option<PathBuf>,
+    /// Dump exactly the files listed in FILE (paths relative to the root, one per line, as
+    /// written by `--write-filelist`) instead of walking the root.
+    ///
+    /// The root is PATH, or the working directory without one, so give the same root the list
+    /// was written against. Listed files that no longer exist are reported as skipped, and
+    /// filters and size limits still apply. Lines starting with `#` are ignored.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["repo", "archive", "compare"])]
+    from_filelist: Option<PathBuf>,
+    /// Append each collected file's path, size, token count, language and skip status to a
+    /// CSV file or an SQLite database, e.g. `sqlite:context.db`.
+    ///
+    /// Rows of one run share a `run` timestamp, so dumps can be compared over time.
+    #[arg(long, value_name = "FORMAT:PATH", value_parser = export::parse_export)]
+    export: Option<export::Export>,
+    /// Split the prompt into parts of at most N tokens, each with its own preamble and file tree.
+    #[arg(long, value_name = "N")]
+    chunk_tokens: Option<usize>,
+    /// With `--chunk-tokens`, write parts to numbered files in DIR instead of stdout.
+    #[arg(long, value_name = "DIR", requires = "chunk_tokens")]
+    chunk_dir: Option<PathBuf>,
+    /// Include a Git section with the current branch, HEAD, recent commits and uncommitted changes.
+    #[arg(long)]
+    git_info: bool,
+    /// Include an Environment section with the OS, architecture and versions of the Rust,
+    /// Node.js, Python and Go toolchains found on `PATH`.
+    #[arg(long)]
+    env_info: bool,
+    /// Include a Repository Summary section up front: languages by lines and tokens, the
+    /// largest directories, and the build tools and frameworks detected from manifests.
+    #[arg(long)]
+    repo_summary: bool,
+    /// Number of recent commits to list with `--git-info`.
+    #[arg(long, value_name = "N", default_value_t = 10, requires = "git_info")]
+    git_log: usize,
+    /// Keep running and regenerate the dump whenever files change (requires `--output` or `--chunk-dir`).
+    #[arg(long, conflicts_with_all = ["repo", "archive"])]
+    watch: bool,
+    /// Quiet period to wait for after a change before regenerating, with `--watch`.
+    #[arg(long, value_name = "MS", default_value_t = 300, requires = "watch")]
+    debounce_ms: u64,
+    /// Remove comments (Rust, Python, JS/TS, Go, and other C-like and `#`-comment languages)
+    /// and/or blank lines from file contents to save tokens.
+    #[arg(long, value_enum, value_name = "WHAT")]
+    strip: Option<strip::Strip>,
+    /// Only include the files of a workspace member (a Cargo, npm/pnpm/yarn or Go package,
+    /// by name or directory) and of the in-repo packages it depends on. Can be repeated.
+    #[arg(long, value_name = "NAME")]
+    workspace_member: Vec<String>,
+    /// Only include FILE and the files it imports or is imported by (from `use`/`mod`, `import`
+    /// and `require` statements in Rust, Python, JS/TS and Go). Can be repeated.
+    #[arg(long, value_name = "FILE")]
+    around: Vec<PathBuf>,
+    /// Number of import hops to follow from the `--around` files.
+    #[arg(long, value_name = "N", default_value_t = 1, requires = "around")]
+    hops: usize,
+    /// Only include files whose contents match REGEX. Can be repeated; files matching any
+    /// of the patterns are included.
+    #[arg(long, value_name = "REGEX")]
+    grep: Vec<Regex>,
+    /// With `--grep`, include only the matching lines of each file and N lines around them,
+    /// instead of whole files.
+    #[arg(long, value_name = "N", requires = "grep")]
+    context_lines: Option<usize>,
+    /// Annotate the file tree with each directory's file count, and each directory's and
+    /// file's approximate tokens with a bar for its share of `--max-tokens` (or of the tokens
+    /// of all included files).
+    #[arg(long)]
+    tree_tokens: bool,
+    /// List files left out of the dump (excluded, skipped or over budget) in the file tree;
+    /// directories with nothing included get a one-line summary of their files.
+    #[arg(long)]
+    summarize_excluded: bool,
+    /// Include lockfiles (`Cargo.lock`, `package-lock.json`, `poetry.lock`, ...) and minified or
+    /// vendored JavaScript and CSS in full, instead of as a summary of their package count and
+    /// direct dependencies, or their size.
+    #[arg(long)]
+    full_lockfiles: bool,
+    /// Replace the contents of files over N tokens with a short summary written by
+    /// `--summary-model`, marked as such in the dump. Summaries are made after redaction.
+    #[arg(long, value_name = "N")]
+    summarize_over: Option<usize>,
+    /// Model that writes the `--summarize-over` summaries (through the same APIs as `chat`).
+    /// Defaults to `--model`.
+    #[arg(
+        long,
+        value_name = "MODEL",
+        env = "PROMPTKIT_SUMMARY_MODEL",
+        requires = "summarize_over"
+    )]
+    summary_model: Option<String>,
+    /// Include files with the same contents (up to whitespace) as an earlier file in full,
+    /// instead of as a one-line reference to it.
+    #[arg(long)]
+    no_dedup: bool,
+    /// Show each file's token count in its `###` header, and list files by token count
+    /// (largest first) on stderr.
+    #[arg(long)]
+    file_tokens: bool,
+    /// Exit with an error if the prompt is over N tokens (after writing it).
+    #[arg(long, value_name = "N", conflicts_with = "watch")]
+    fail_if_tokens_over: Option<usize>,
+    /// Exit with an error if any file was skipped (too large, binary, over budget, ...).
+    #[arg(long, conflicts_with = "watch")]
+    fail_if_skipped: bool,
+    /// Rename files and directories to placeholders (`dir_a/file_1.rs`), also where their
+    /// names appear as words in file contents, and write the mapping back to the real names
+    /// to MAP. Names like `src` or `main` are kept.
+    #[arg(long, value_name = "MAP", conflicts_with_all = ["git_info", "repo"])]
+    anonymize: Option<PathBuf>,
+    /// With `--anonymize`, also replace string literals with placeholders (`"str_1"`).
+    #[arg(long, requires = "anonymize")]
+    anonymize_strings: bool,
+    /// Add a line under each file's header with its size, line count, modification time and
+    /// last Git commit.
+    #[arg(long, conflicts_with = "anonymize")]
+    file_info: bool,
+    /// Format of the stats written to stderr.
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = stats::Format::Text)]
+    stats_format: stats::Format,
+    /// Do not redact detected secrets (API keys, private keys, `.env` values) from file contents.
+    #[arg(long)]
+    no_redact: bool,
+}
+
+impl DumpArgs {
+    /// Joins TASK, `--task` fragments and `--task-file` contents, in that order.
+    fn read_task(&self) -> Result<String> {
+        let mut fragments: Vec<String> = self.task.iter().cloned().collect();
+        fragments.extend(self.task_fragments.iter().cloned());
+        for path in &self.task_file {
+            let text = if path.as_os_str() == "-" {
+                io::read_to_string(io::stdin()).context("failed to read task from stdin")?
+            } else {
+                fs::read_to_string(path)
+                    .with_context(|| format!("failed to read task file {}", path.display()))?
+            };
+            fragments.push(text);
+        }
+
+        let task = fragments
+            .iter()
+            .map(|fragment| fragment.trim())
+            .filter(|fragment| !fragment.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        if task.is_empty() {
+            bail!("the task is empty");
+        }
+        Ok(task)
+    }
+}
+
+/// Options controlling which files are collected, shared by `dump` and `stats`.
+#[derive(Args, Debug)]
+struct CollectArgs {
+    /// Only include files matching GLOB (gitignore syntax, relative to the root). Can be repeated.
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+    /// Leave out files matching GLOB (gitignore syntax, relative to the root). Can be repeated.
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+    /// Leave out a curated set of files for the ecosystems (Rust, JS/TS, Python, Go, JVM)
+    /// detected from manifests at the root or one directory down. Can be repeated.
+    ///
+    /// `no-tests`: test files and directories, fixtures and snapshots. `no-generated`:
+    /// lockfiles, generated protobuf/OpenAPI code, build output and minified assets.
+    /// `code-only`: both, plus docs, data files and assets.
+    #[arg(long, value_enum, value_name = "PRESET")]
+    preset: Vec<presets::Preset>,
+    /// Only include files changed since WHEN: a duration before now like `30m`, `12h`, `7d`,
+    /// `2w` or `1d12h`, or a UTC date like `2026-01-31` or `2026-01-31 09:30`.
+    #[arg(long, value_name = "WHEN", value_parser = recency::parse_since)]
+    since: Option<SystemTime>,
+    /// How `--since` tells when a file changed: `mtime` by its modification time, `git` by the
+    /// commits that touched it. With `git`, uncommitted and untracked files count as changed.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "SOURCE",
+        default_value_t = recency::Source::Mtime,
+        requires = "since"
+    )]
+    since_source: recency::Source,
+    /// Maximum file size to include, in bytes or with a `k`/`m` suffix (multiples of 1024).
+    /// Defaults to 64000.
+    ///
+    /// `GLOB=SIZE` sets the limit for files matching GLOB (gitignore syntax, relative to the
+    /// root), e.g. `--max-file-size '*.json=8k' --max-file-size 'src/**=256k'`. The last
+    /// matching pattern wins, and patterns given here win over `[max_file_sizes]` in the config.
+    /// Can be repeated.
+    #[arg(long, value_name = "[GLOB=]SIZE", value_parser = parse_size_limit)]
+    max_file_size: Vec<SizeLimit>,
+    /// Include only the first N lines of log files (see `--log-glob`), however large they are.
+    #[arg(long, value_name = "N", conflicts_with = "tail")]
+    head: Option<usize>,
+    /// Include only the last N lines of log files (see `--log-glob`), however large they are.
+    #[arg(long, value_name = "N")]
+    tail: Option<usize>,
+    /// Files `--head` and `--tail` apply to (gitignore syntax). Can be repeated.
+    #[arg(long, value_name = "GLOB", default_values = ["*.log", "*.out"])]
+    log_glob: Vec<String>,
+    /// Follow symbolic links to files and directories. Links that lead back to an ancestor
+    /// directory are skipped.
+    ///
+    /// Without this flag, symlinks are listed in the file tree as `link -> target`.
+    #[arg(long)]
+    follow_symlinks: bool,
+    /// Include the first `--max-file-size` bytes (cut at a line break) of larger files,
+    /// followed by a truncation marker, instead of skipping them.
+    #[arg(long)]
+    truncate_large_files: bool,
+    /// Decode files that are neither UTF-8 nor a detected encoding (UTF-16, Windows-1252)
+    /// by replacing invalid sequences, instead of skipping them. Binary files are still skipped.
+    #[arg(long)]
+    lossy_utf8: bool,
+    /// Do not skip files ignored by `.gitignore`, the global Git excludes file or
+    /// `.git/info/exclude`. Directories like `target` and `node_modules` are still skipped
+    /// unless given as a path.
+    #[arg(long)]
+    no_gitignore: bool,
+    /// Do not apply ignore files from the directories above the root.
+    #[arg(long)]
+    no_ignore_parent: bool,
+    /// Include hidden files and directories (the default).
+    #[arg(long, overrides_with = "no_hidden")]
+    hidden: bool,
+    /// Skip hidden files and directories (names starting with `.`).
+    #[arg(long, overrides_with = "hidden")]
+    no_hidden: bool,
+    /// Write paths with the platform's separator (`\` on Windows) instead of `/`.
+    #[arg(long)]
+    native_separators: bool,
+    /// Only token counts and sizes are needed: files with a cached count are not read, and
+    /// are collected with empty contents.
+    #[arg(skip)]
+    counts_only: bool,
+}
+
+/// A `--max-file-size` value.
+#[derive(Clone, Debug)]
+struct SizeLimit {
+    glob: Option<String>,
+    bytes: usize,
+}
+
+fn parse_size_limit(arg: &str) -> Result<SizeLimit, String> {
+    let (glob, size) = match arg.rsplit_once('=') {
+        Some((glob, size)) => (Some(glob.to_string()), size),
+        None => (None, arg),
+    };
+    Ok(SizeLimit {
+        glob,
+        bytes: parse_size(size)?,
+    })
+}
+
+/// Parses `64000`, `8k` or `1m` (multiples of 1024).
+fn parse_size(size: &str) -> Result<usize, String> {
+    let size = size.trim();
+    let (digits, unit) = match size.char_indices().last() {
+        Some((idx, 'k' | 'K')) => (&size[..idx], 1024),
+        Some((idx, 'm' | 'M')) => (&size[..idx], 1024 * 1024),
+        _ => (size, 1),
+    };
+    digits
+        .trim()
+        .replace('_', "")
+        .parse::<usize>()
+        .map(|n| n * unit)
+        .map_err(|_| format!("invalid size {size:?}; expected e.g. 64000, 8k or 1m"))
+}
+
+#[derive(Args, Debug)]
+struct ChatArgs {
+    #[command(flatten)]
+    dump: DumpArgs,
+    /// API flavor. Defaults to `anthropic` for `claude-*` models and `openai` otherwise.
+    #[arg(long, value_enum)]
+    provider: Option<llm::Provider>,
+    /// Base URL of the API, e.g. for an OpenAI-compatible server.
+    #[arg(long, value_name = "URL")]
+    base_url: Option<String>,
+    /// Maximum number of tokens in the reply.
+    #[arg(long, value_name = "N", default_value_t = 8192)]
+    max_output_tokens: u32,
+    /// Number of retries on connection errors, rate limits and server errors.
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    retries: u32,
+}
+
+#[derive(Args, Debug)]
+struct StatsArgs {
+    /// Directory to analyze. Defaults to the current working directory.
+    path: Option<PathBuf>,
+    #[command(flatten)]
+    collect: CollectArgs,
+    /// Column to sort rows by (descending, except for `name`).
+    #[arg(long, value_enum, default_value_t = stats::SortKey::Tokens)]
+    sort: stats::SortKey,
+    /// Only show the first N rows of each table.
+    #[arg(long, value_name = "N")]
+    top: Option<usize>,
+    /// Number of leading path components used to group directories.
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    depth: usize,
+    /// Model to estimate the input cost for.
+    #[arg(long, env = "PROMPTKIT_MODEL")]
+    model: Option<String>,
+    /// Also print the Repository Summary that `dump --repo-summary` adds to the prompt.
+    #[arg(long)]
+    repo_summary: bool,
+}
+
+#[derive(Args, Debug)]
+struct TreeArgs {
+    /// Directory to show. Defaults to the current working directory.
+    path: Option<PathBuf>,
+    #[command(flatten)]
+    collect: CollectArgs,
+    /// Collapse directories deeper than N levels into one line with their totals.
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+    /// Do not annotate entries with token and byte counts.
+    #[arg(long)]
+    no_sizes: bool,
+}
+
+#[derive(Args, Debug)]
+struct RenderArgs {
+    /// Template to fill. Reads stdin if '-'.
+    template: PathBuf,
+    /// Value for `{{NAME}}` in the template. Can be repeated.
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = template::parse_var)]
+    vars: Vec<(String, String)>,
+    /// File to embed at `{{files}}`. Can be repeated.
+    #[arg(long = "file", value_name = "PATH")]
+    files: Vec<PathBuf>,
+    /// Write the prompt to FILE instead of stdout.
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+    /// Do not redact detected secrets from embedded files.
+    #[arg(long)]
+    no_redact: bool,
+}
+
+#[derive(Args, Debug)]
+struct DiffsplitArgs {
+    /// Unified diff to split (e.g. from `git diff`). Reads stdin if omitted or '-'.
+    input: Option<PathBuf>,
+    /// Instruction included at the end of every part.
+    #[arg(
+        long,
+        value_name = "TEXT",
+        default_value = "Review the changes above. Point out bugs, risky changes and missing tests."
+    )]
+    task: String,
+    /// Pack file diffs into parts of at most N tokens instead of one part per file.
+    /// Files that alone exceed N are split at hunk boundaries.
+    #[arg(long, value_name = "N")]
+    chunk_tokens: Option<usize>,
+    /// Write parts to numbered files in DIR instead of stdout.
+    #[arg(long, value_name = "DIR")]
+    chunk_dir: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct HistoryArgs {
+    /// Number of most recent entries to show.
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    limit: usize,
+}
+
+#[derive(Args, Debug)]
+struct SnippetArgs {
+    #[command(subcommand)]
+    command: SnippetCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum SnippetCommand {
+    /// Save a snippet from FILE, or from stdin without it.
+    Add {
+        /// Name to insert it by, with `--snippet NAME`.
+        name: String,
+        file: Option<PathBuf>,
+        /// Replace an existing snippet of the same name.
+        #[arg(long)]
+        force: bool,
+    },
+    /// List saved snippets with their token counts and first lines.
+    List,
+    /// Print a snippet to stdout.
+    Use { name: String },
+}
+
+#[derive(Args, Debug)]
+struct RerunArgs {
+    /// Entry id, as listed by `history`.
+    id: u64,
+}
+
+#[derive(Args, Debug)]
+struct McpArgs {
+    /// Directory the tools are scoped to. Defaults to the current working directory.
     path: Option<PathBuf>,
-    /// Maximum file size (in bytes) to include in the dump.
-    #[arg(long, value_name = "BYTES", default_value_t = 64_000)]
-    max_file_size: usize,
+}
+
+#[derive(Args, Debug)]
+struct CompletionsArgs {
+    /// Shell to generate the script for.
+    #[arg(value_enum)]
+    shell: clap_complete::Shell,
 }
 
 #[derive(Args, Debug)]
@@ -61,116 +664,1920 @@ struct CountArgs {
 struct FileDump {
     relative_path: String,
     contents: String,
+    /// Fence language, if detected.
+    language: Option<String>,
+    /// Size on disk (flattened size for notebooks), if `contents` holds only the head of the file.
+    truncated_from: Option<u64>,
+    /// Encoding the file was transcoded from, if it was not plain UTF-8.
+    encoding: Option<decode::Encoding>,
+    /// Tokens of `contents`, shown in the file's header with `--file-tokens`.
+    tokens: Option<usize>,
+    /// Last modification time on disk.
+    modified: Option<SystemTime>,
+    /// Size, line count, mtime and last commit, shown under the header with `--file-info`.
+    info: Option<String>,
+    /// Tokens of the original contents, if `contents` is a `--summarize-over` summary.
+    summarized_from: Option<usize>,
+    /// Path of an earlier file with the same contents, which are then left out of this one.
+    duplicate_of: Option<String>,
+    /// How the file differs from the old version, with `--compare`.
+    change: Option<compare::Change>,
+    /// What kind of file `contents` summarizes, and its size in bytes, if it is a lockfile or
+    /// a minified or vendored asset left out without `--full-lockfiles`.
+    condensed: Option<(lockfiles::Kind, usize)>,
+    /// The file `contents` were read from unchanged, which keys its count in the token cache.
+    /// Cleared whenever `contents` change.
+    source: Option<cache::Source>,
 }
 
 struct SkippedFile {
     relative_path: String,
     reason: SkipReason,
+    /// Size when it was skipped, if it got as far as being looked at.
+    bytes: Option<u64>,
+}
+
+/// A symlink that was not followed, shown in the file tree as `link -> target`.
+struct Symlink {
+    relative_path: String,
+    target: String,
+}
+
+/// A file left out of the dump, summarized in the file tree with `--summarize-excluded`.
+struct OmittedFile {
+    relative_path: String,
+    bytes: u64,
+}
+
+/// Everything found under a root by [`collect_files`].
+struct Collection {
+    files: Vec<FileDump>,
+    skipped: Vec<SkippedFile>,
+    symlinks: Vec<Symlink>,
+    /// Files left out by `--include` / `--exclude` / `--since`.
+    filtered: Vec<String>,
+    /// Sizes of paths with no file on disk to look up: archive entries, and filtered files
+    /// once anonymized.
+    sizes: HashMap<String, u64>,
+}
+
+enum SkipReason {
+    TooLarge(u64),
+    NonUtf8,
+    OverBudget,
+    /// Listed by `--from-filelist`, but not found under the root.
+    Missing,
+    /// A followed symlink pointing back to this ancestor directory.
+    SymlinkLoop(String),
+    /// A `[preprocessors]` command failed.
+    Preprocessor(String),
+    Io(String),
+}
+
+impl SkipReason {
+    /// Stable identifier for machine-readable output.
+    fn kind(&self) -> &'static str {
+        match self {
+            SkipReason::TooLarge(_) => "too_large",
+            SkipReason::NonUtf8 => "non_utf8",
+            SkipReason::OverBudget => "over_budget",
+            SkipReason::Missing => "missing",
+            SkipReason::SymlinkLoop(_) => "symlink_loop",
+            SkipReason::Preprocessor(_) => "preprocessor",
+            SkipReason::Io(_) => "io",
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::TooLarge(len) => write!(f, "exceeds size limit ({} bytes)", len),
+            SkipReason::NonUtf8 => write!(f, "binary or undetected encoding"),
+            SkipReason::OverBudget => write!(f, "exceeds token budget"),
+            SkipReason::Missing => write!(f, "listed but not found"),
+            SkipReason::SymlinkLoop(ancestor) => write!(f, "symlink loop back to {ancestor}"),
+            SkipReason::Preprocessor(err) => write!(f, "preprocessor failed: {err}"),
+            SkipReason::Io(err) => write!(f, "I/O error: {err}"),
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let argv: Vec<String> = env::args_os()
+        .skip(1)
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    let (cli, config) = parse_cli(&argv)?;
+    if !cli.no_cache {
+        cache::enable();
+    }
+    if !cli.quiet {
+        progress::enable();
+    }
+
+    match cli.command {
+        Command::Dump(args) => run_dump(args, &config, &argv)?,
+        Command::Chat(args) => run_chat(args, &config)?,
+        Command::Count(args) => run_count(args)?,
+        Command::Diffsplit(args) => run_diffsplit(args)?,
+        Command::History(args) => run_history(args)?,
+        Command::Rerun(args) => run_rerun(args)?,
+        Command::Mcp(args) => run_mcp(args, config)?,
+        Command::Stats(args) => run_stats(args, &config)?,
+        Command::Tree(args) => run_tree(args, &config)?,
+        Command::Render(args) => run_render(args, &config)?,
+        Command::Snippet(args) => run_snippet(args)?,
+        Command::Completions(args) => {
+            completions::generate(args.shell, cli_command(), &mut io::stdout())?
+        }
+        Command::Man => completions::man(cli_command(), &mut io::stdout())?,
+    }
+
+    if let Err(err) = cache::save() {
+        eprintln!("Warning: failed to save the token cache: {err:#}");
+    }
+    Ok(())
+}
+
+/// The clap definition of the command line, named as the binary is installed.
+fn cli_command() -> clap::Command {
+    Cli::command().name("promptkit").bin_name("promptkit")
+}
+
+/// Parses `argv` (without the program name) and loads the config, expanding `--profile`.
+fn parse_cli(argv: &[String]) -> Result<(Cli, Config)> {
+    let parse = |argv: &[String]| {
+        Cli::parse_from(std::iter::once("promptkit").chain(argv.iter().map(String::as_str)))
+    };
+    let cli = parse(argv);
+    let config = Config::load(cli.config.as_deref())?;
+
+    let profile = match &cli.command {
+        Command::Dump(args) => args.profile.as_deref(),
+        Command::Chat(args) => args.dump.profile.as_deref(),
+        _ => None,
+    };
+    let Some(name) = profile else {
+        return Ok((cli, config));
+    };
+    let expanded = splice_args(argv, subcommand_index(argv) + 1, config.profile(name)?);
+    Ok((parse(&expanded), config))
+}
+
+/// Position of the subcommand in `argv`, skipping global options and their values.
+fn subcommand_index(argv: &[String]) -> usize {
+    let mut idx = 0;
+    while let Some(arg) = argv.get(idx) {
+        if arg == "--config" {
+            idx += 2;
+        } else if arg.starts_with('-') {
+            idx += 1;
+        } else {
+            break;
+        }
+    }
+    idx
+}
+
+/// Inserts `extra` into `argv` at `at`.
+fn splice_args(argv: &[String], at: usize, extra: &[String]) -> Vec<String> {
+    let mut spliced = argv[..at.min(argv.len())].to_vec();
+    spliced.extend(extra.iter().cloned());
+    spliced.extend(argv[at.min(argv.len())..].iter().cloned());
+    spliced
+}
+
+/// `argv` is the command line without the program name, recorded in the history.
+fn run_dump(args: DumpArgs, config: &Config, argv: &[String]) -> Result<()> {
+    if args.watch && args.output.is_none() && args.chunk_dir.is_none() {
+        bail!("--watch requires --output or --chunk-dir");
+    }
+
+    let task = args.read_task()?;
+    let tokenizer = o200k_base().context("failed to load o200k_base tokenizer")?;
+    let dump = dump_once(&args, &task, config, &tokenizer, argv)?;
+    check_limits(&dump, &args)?;
+
+    if args.watch {
+        let ignored: Vec<PathBuf> = [
+            &args.output,
+            &args.chunk_dir,
+            &args.manifest,
+            &args.write_filelist,
+        ]
+        .into_iter()
+        .flatten()
+        .map(|path| path.canonicalize())
+        .collect::<io::Result<_>>()?;
+        let root_dir = dump.root_dir.clone();
+        let mut previous = dump;
+        let mut previous_fingerprints = previous.fingerprints()?;
+        watch::watch(
+            &root_dir,
+            &ignored,
+            Duration::from_millis(args.debounce_ms),
+            |changed| {
+                run_pre_dump_hook(&args, config)?;
+                let mut dump = build_dump(&args, &task, config, &tokenizer)?;
+                let fingerprints = dump.fingerprints()?;
+                if fingerprints == previous_fingerprints {
+                    return Ok(());
+                }
+                let part_paths = write_dump(&mut dump, &args, &tokenizer)?;
+                if let Some(path) = &args.manifest {
+                    write_manifest(&dump, path, &tokenizer)?;
+                }
+                if let Some(path) = &args.write_filelist {
+                    filelist::write(path, &dump.selection)?;
+                }
+                let tokens = dump.token_count();
+                eprintln!(
+                    "Updated: changed={}, tokens={} ({:+}), files_included={}, files_skipped={}",
+                    changed.len(),
+                    tokens,
+                    tokens as i64 - previous.token_count() as i64,
+                    dump.files.len(),
+                    dump.skipped.len()
+                );
+                run_post_dump_hook(&dump, &args, config, &part_paths)?;
+                previous = dump;
+                previous_fingerprints = fingerprints;
+                if let Err(err) = cache::save() {
+                    eprintln!("Warning: failed to save the token cache: {err:#}");
+                }
+                Ok(())
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Fails if the dump crossed a `--fail-if-*` threshold. Runs after the dump was written, so
+/// the output is still there to inspect.
+fn check_limits(dump: &Dump, args: &DumpArgs) -> Result<()> {
+    if let Some(limit) = args.fail_if_tokens_over
+        && dump.token_count() > limit
+    {
+        bail!(
+            "prompt is {} tokens, over --fail-if-tokens-over {}",
+            with_thousands(dump.token_count()),
+            with_thousands(limit)
+        );
+    }
+    if args.fail_if_skipped && !dump.skipped.is_empty() {
+        bail!(
+            "{} files were skipped (--fail-if-skipped)",
+            dump.skipped.len()
+        );
+    }
+    Ok(())
+}
+
+/// Builds, writes and reports a dump, recording it in the history if enabled.
+fn dump_once(
+    args: &DumpArgs,
+    task: &str,
+    config: &Config,
+    tokenizer: &CoreBPE,
+    argv: &[String],
+) -> Result<Dump> {
+    run_pre_dump_hook(args, config)?;
+    let mut dump = build_dump(args, task, config, tokenizer)?;
+    let part_paths = write_dump(&mut dump, args, tokenizer)?;
+    report_dump(&dump, args, config, tokenizer, &part_paths)?;
+    if config.record_history {
+        let id = history::record(argv, &dump.files, dump.token_count())?;
+        eprintln!("History: id={}", id);
+    }
+    run_post_dump_hook(&dump, args, config, &part_paths)?;
+    Ok(dump)
+}
+
+/// Runs the `pre-dump` hook from the config, if any, with where the prompt will be written.
+fn run_pre_dump_hook(args: &DumpArgs, config: &Config) -> Result<()> {
+    let Some(command) = &config.hooks.pre_dump else {
+        return Ok(());
+    };
+    let mut vars = Vec::new();
+    if let Some(path) = &args.output {
+        vars.push(("PROMPTKIT_OUTPUT", path.display().to_string()));
+    }
+    if let Some(dir) = &args.chunk_dir {
+        vars.push(("PROMPTKIT_CHUNK_DIR", dir.display().to_string()));
+    }
+    hooks::run("pre-dump", command, &vars, |_| Ok(()))
+}
+
+/// Runs the `post-dump` hook from the config, if any, with the prompt on stdin (parts joined
+/// as on stdout) and its paths and stats in the environment.
+fn run_post_dump_hook(
+    dump: &Dump,
+    args: &DumpArgs,
+    config: &Config,
+    part_paths: &[PathBuf],
+) -> Result<()> {
+    let Some(command) = &config.hooks.post_dump else {
+        return Ok(());
+    };
+    let bytes: usize = dump.files.iter().map(|file| file.contents.len()).sum();
+    let mut vars = vec![
+        ("PROMPTKIT_ROOT", dump.root_label.clone()),
+        ("PROMPTKIT_TOKENS", dump.token_count().to_string()),
+        ("PROMPTKIT_FILES_INCLUDED", dump.files.len().to_string()),
+        ("PROMPTKIT_FILES_SKIPPED", dump.skipped.len().to_string()),
+        ("PROMPTKIT_BYTES", bytes.to_string()),
+        ("PROMPTKIT_PARTS", dump.parts.len().to_string()),
+    ];
+    if let Some(path) = &args.output {
+        vars.push(("PROMPTKIT_OUTPUT", path.display().to_string()));
+    }
+    if let Some(dir) = &args.chunk_dir {
+        vars.push(("PROMPTKIT_CHUNK_DIR", dir.display().to_string()));
+        let paths: Vec<String> = part_paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        vars.push(("PROMPTKIT_PART_FILES", paths.join("\n")));
+    }
+
+    hooks::run("post-dump", command, &vars, |stdin| {
+        write_joined(dump.parts.len(), stdin, |idx, out| {
+            dump.stream_part(idx, out, None)?;
+            Ok(())
+        })
+    })
+}
+
+fn run_history(args: HistoryArgs) -> Result<()> {
+    let entries = history::load()?;
+    if entries.is_empty() {
+        eprintln!("No recorded dumps; set `record_history = true` in the config to record them.");
+        return Ok(());
+    }
+    print!("{}", history::render(&entries, args.limit)?);
+    Ok(())
+}
+
+fn run_snippet(args: SnippetArgs) -> Result<()> {
+    match args.command {
+        SnippetCommand::Add { name, file, force } => {
+            let text = match file {
+                Some(path) if path.as_os_str() != "-" => fs::read_to_string(&path)
+                    .with_context(|| format!("failed to read {}", path.display()))?,
+                _ => io::read_to_string(io::stdin()).context("failed to read from stdin")?,
+            };
+            let path = snippets::add(&name, &text, force)?;
+            eprintln!("Saved: snippet={name}, path={}", path.display());
+        }
+        SnippetCommand::List => {
+            let snippets = snippets::list()?;
+            if snippets.is_empty() {
+                eprintln!("No snippets; save one with `promptkit snippet add NAME FILE`.");
+                return Ok(());
+            }
+            let tokenizer = o200k_base().context("failed to load o200k_base tokenizer")?;
+            let width = snippets
+                .iter()
+                .map(|(name, _)| name.len())
+                .max()
+                .unwrap_or(0);
+            for (name, text) in &snippets {
+                let first_line = text.lines().find(|line| !line.trim().is_empty());
+                println!(
+                    "{name:<width$}  {:>6} tokens  {}",
+                    with_thousands(count_tokens(&tokenizer, text)),
+                    first_line.unwrap_or("").trim()
+                );
+            }
+        }
+        SnippetCommand::Use { name } => print!("{}", snippets::read(&name)?),
+    }
+    Ok(())
+}
+
+fn run_rerun(args: RerunArgs) -> Result<()> {
+    let entry = history::find(args.id)?;
+    env::set_current_dir(&entry.cwd)
+        .with_context(|| format!("failed to enter {}", entry.cwd.display()))?;
+
+    let cli =
+        Cli::try_parse_from(std::iter::once("promptkit".to_string()).chain(entry.args.clone()))
+            .with_context(|| {
+                format!(
+                    "failed to parse the arguments of history entry {}",
+                    entry.id
+                )
+            })?;
+    let Command::Dump(dump_args) = cli.command else {
+        bail!("history entry {} is not a dump", entry.id);
+    };
+    if dump_args.watch {
+        bail!(
+            "history entry {} used --watch; run it directly instead",
+            entry.id
+        );
+    }
+    let config = Config::load(cli.config.as_deref())?;
+
+    let task = dump_args.read_task()?;
+    let tokenizer = o200k_base().context("failed to load o200k_base tokenizer")?;
+    let dump = dump_once(&dump_args, &task, &config, &tokenizer, &entry.args)?;
+    check_limits(&dump, &dump_args)?;
+    eprintln!(
+        "Rerun: id={}, files_changed={}, tokens={} ({:+})",
+        entry.id,
+        history::files_hash(&dump.files) != entry.files_hash,
+        dump.token_count(),
+        dump.token_count() as i64 - entry.tokens as i64
+    );
+    Ok(())
+}
+
+/// Renders the parts straight into `--chunk-dir`, `--output` or stdout, counting their
+/// tokens on the way; returns the part file paths.
+fn write_dump(dump: &mut Dump, args: &DumpArgs, tokenizer: &CoreBPE) -> Result<Vec<PathBuf>> {
+    let mut part_tokens = Vec::new();
+    progress::start(progress::Phase::Rendering);
+    let part_paths = write_parts(
+        dump.parts.len(),
+        args.chunk_dir.as_deref(),
+        args.output.as_deref(),
+        |idx, out| {
+            part_tokens.push(dump.stream_part(idx, out, Some(tokenizer))?);
+            Ok(())
+        },
+    );
+    progress::finish();
+    dump.part_tokens = part_tokens;
+    part_paths
+}
+
+/// Writes `count` parts with `write_part` into numbered part files in `dir`, or separated by
+/// delimiters into `output` (stdout if `None`). Returns the part file paths.
+fn write_parts(
+    count: usize,
+    dir: Option<&Path>,
+    output: Option<&Path>,
+    mut write_part: impl FnMut(usize, &mut dyn Write) -> Result<()>,
+) -> Result<Vec<PathBuf>> {
+    let mut part_paths = Vec::new();
+    match dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create directory {}", dir.display()))?;
+            let width = count.to_string().len();
+            for idx in 0..count {
+                let path = dir.join(format!("part-{:0width$}.md", idx + 1));
+                let mut file = io::BufWriter::new(
+                    fs::File::create(&path)
+                        .with_context(|| format!("failed to create {}", path.display()))?,
+                );
+                write_part(idx, &mut file)
+                    .and_then(|()| Ok(file.flush()?))
+                    .with_context(|| format!("failed to write {}", path.display()))?;
+                part_paths.push(path);
+            }
+        }
+        None => {
+            let mut out: Box<dyn Write> = match output {
+                Some(path) => Box::new(io::BufWriter::new(
+                    fs::File::create(path)
+                        .with_context(|| format!("failed to create {}", path.display()))?,
+                )),
+                None => Box::new(io::BufWriter::new(io::stdout().lock())),
+            };
+            write_joined(count, &mut out, write_part)?;
+            out.flush()?;
+        }
+    }
+
+    Ok(part_paths)
+}
+
+/// Writes `count` parts with `write_part` into `out`, separated by part delimiters.
+fn write_joined(
+    count: usize,
+    out: &mut dyn Write,
+    mut write_part: impl FnMut(usize, &mut dyn Write) -> Result<()>,
+) -> Result<()> {
+    for idx in 0..count {
+        if idx > 0 {
+            writeln!(out, "{}", part_delimiter(idx + 1, count))?;
+        }
+        write_part(idx, out)?;
+    }
+    Ok(())
+}
+
+fn run_chat(args: ChatArgs, config: &Config) -> Result<()> {
+    if args.dump.chunk_tokens.is_some() {
+        bail!("--chunk-tokens is not supported by chat; use `dump` and send the parts yourself");
+    }
+    if args.dump.watch {
+        bail!("--watch is not supported by chat");
+    }
+    let Some(model) = args.dump.model.as_deref() else {
+        bail!("chat requires --model (or PROMPTKIT_MODEL)");
+    };
+
+    let task = args.dump.read_task()?;
+    let tokenizer = o200k_base().context("failed to load o200k_base tokenizer")?;
+    let mut dump = build_dump(&args.dump, &task, config, &tokenizer)?;
+    // The request body carries the whole prompt, so this is the one place it is held in memory.
+    let mut prompt = Vec::new();
+    dump.part_tokens = vec![dump.stream_part(0, &mut prompt, Some(&tokenizer))?];
+    let prompt = String::from_utf8(prompt)?;
+
+    if let Some(path) = &args.dump.output {
+        fs::write(path, &prompt).with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    report_dump(&dump, &args.dump, config, &tokenizer, &[])?;
+    check_limits(&dump, &args.dump)?;
+
+    let request = llm::ChatRequest {
+        provider: args
+            .provider
+            .unwrap_or_else(|| llm::Provider::for_model(model)),
+        model,
+        base_url: args.base_url.as_deref(),
+        prompt: &prompt,
+        max_output_tokens: args.max_output_tokens,
+        retries: args.retries,
+    };
+    let mut stdout = io::stdout().lock();
+    let usage = llm::send(&request, &mut stdout)?;
+    writeln!(stdout)?;
+
+    eprintln!(
+        "Usage: model={}, input_tokens={}, output_tokens={}",
+        model, usage.input_tokens, usage.output_tokens
+    );
+    Ok(())
+}
+
+/// Writes the manifest (if requested) and stats info to stderr.
+fn report_dump(
+    dump: &Dump,
+    args: &DumpArgs,
+    config: &Config,
+    tokenizer: &CoreBPE,
+    part_paths: &[PathBuf],
+) -> Result<()> {
+    let token_count = dump.token_count();
+    let included_count = dump.files.len();
+    let skipped_count = dump.skipped.len();
+    let total_bytes: usize = dump.files.iter().map(|file| file.contents.len()).sum();
+
+    if let Some(path) = &args.manifest {
+        write_manifest(dump, path, tokenizer)?;
+    }
+    if let Some(path) = &args.write_filelist {
+        filelist::write(path, &dump.selection)?;
+    }
+
+    let per_file_tokens: Vec<usize> = match dump.files.iter().map(|file| file.tokens).collect() {
+        Some(tokens) => tokens,
+        None => file_tokens(tokenizer, &dump.files),
+    };
+    // Everything that is not file contents: preamble, file tree, headers, fences and task.
+    let content_tokens: usize = per_file_tokens.iter().sum();
+    let overhead_tokens = token_count.saturating_sub(content_tokens);
+    let mut top_files: Vec<(&str, usize)> = dump
+        .files
+        .iter()
+        .map(|file| file.relative_path.as_str())
+        .zip(per_file_tokens.iter().copied())
+        .collect();
+    top_files.sort_by_key(|&(path, tokens)| (std::cmp::Reverse(tokens), path));
+    top_files.truncate(TOP_FILES);
+
+    if let Some(export) = &args.export {
+        let run = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        export::write(
+            export,
+            run,
+            &dump.root_label,
+            &dump.files,
+            &per_file_tokens,
+            &dump.skipped,
+        )?;
+    }
+
+    if args.stats_format == stats::Format::Json {
+        let summary = stats::Summary {
+            tokens: token_count,
+            part_tokens: args.chunk_tokens.map(|_| dump.part_tokens.as_slice()),
+            tokens_stripped: dump.tokens_stripped,
+            model: args.model.as_deref(),
+            input_cost: args
+                .model
+                .as_deref()
+                .and_then(|model| pricing::input_cost(token_count, model, &config.prices)),
+            content_tokens,
+            overhead_tokens,
+            top_files: top_files
+                .iter()
+                .map(|&(path, tokens)| stats::FileTokens { path, tokens })
+                .collect(),
+            ..stats::Summary::new(&dump.files, &dump.skipped, &per_file_tokens)
+        };
+        eprintln!("{}", serde_json::to_string(&summary)?);
+        return Ok(());
+    }
+
+    if !dump.skipped.is_empty() {
+        for skipped_file in &dump.skipped {
+            eprintln!(
+                "Skipped: path={}, reason={}",
+                skipped_file.relative_path, skipped_file.reason
+            );
+        }
+    }
+
+    for file in &dump.files {
+        if let Some(encoding) = file.encoding {
+            eprintln!(
+                "Decoded: path={}, encoding={}",
+                file.relative_path,
+                encoding.name()
+            );
+        }
+        if let Some(original) = file.truncated_from {
+            eprintln!(
+                "Truncated: path={}, bytes_included={}, bytes_total={}",
+                file.relative_path,
+                file.contents.len(),
+                original
+            );
+        }
+        if let Some(original) = &file.duplicate_of {
+            eprintln!(
+                "Duplicate: path={}, same_as={}",
+                file.relative_path, original
+            );
+        }
+        if let Some(original) = file.summarized_from {
+            eprintln!(
+                "Summarized: path={}, tokens_total={}",
+                file.relative_path, original
+            );
+        }
+        if let Some((kind, bytes)) = file.condensed {
+            eprintln!(
+                "Condensed: path={}, kind={}, bytes_total={}",
+                file.relative_path,
+                kind.label(),
+                bytes
+            );
+        }
+    }
+
+    if args.file_tokens {
+        let mut by_tokens: Vec<&FileDump> = dump.files.iter().collect();
+        by_tokens.sort_by_key(|file| std::cmp::Reverse(file.tokens));
+        for file in by_tokens {
+            eprintln!(
+                "Tokens: path={}, tokens={}",
+                file.relative_path,
+                file.tokens.unwrap_or_default()
+            );
+        }
+    }
+
+    if !args.file_tokens {
+        for (idx, (path, tokens)) in top_files.iter().enumerate() {
+            eprintln!("Top: rank={}, path={}, tokens={}", idx + 1, path, tokens);
+        }
+    }
+
+    for redaction in &dump.redactions {
+        eprintln!("Redacted: {}", redaction);
+    }
+
+    if let Some(limit) = args.chunk_tokens {
+        let count = dump.parts.len();
+        for (idx, (range, tokens)) in dump.parts.iter().zip(&dump.part_tokens).enumerate() {
+            let mut line = format!(
+                "Part: index={}/{}, tokens={}, files={}",
+                idx + 1,
+                count,
+                tokens,
+                range.len()
+            );
+            if let Some(path) = part_paths.get(idx) {
+                write!(line, ", path={}", path.display())?;
+            }
+            if *tokens > limit {
+                line.push_str(", over_limit=true");
+            }
+            eprintln!("{}", line);
+        }
+    }
+
+    let truncated_count = dump
+        .files
+        .iter()
+        .filter(|file| file.truncated_from.is_some())
+        .count();
+    let mut stats = format!(
+        "Stats: tokens={}, files_included={}, files_skipped={}, bytes={}",
+        token_count, included_count, skipped_count, total_bytes
+    );
+    write!(
+        stats,
+        ", content_tokens={}, overhead_tokens={}",
+        content_tokens, overhead_tokens
+    )?;
+    if truncated_count > 0 {
+        write!(stats, ", files_truncated={}", truncated_count)?;
+    }
+    let summarized_count = dump
+        .files
+        .iter()
+        .filter(|file| file.summarized_from.is_some())
+        .count();
+    if summarized_count > 0 {
+        write!(stats, ", files_summarized={}", summarized_count)?;
+    }
+    let duplicate_count = dump
+        .files
+        .iter()
+        .filter(|file| file.duplicate_of.is_some())
+        .count();
+    if duplicate_count > 0 {
+        write!(stats, ", files_deduplicated={}", duplicate_count)?;
+    }
+    let condensed_count = dump
+        .files
+        .iter()
+        .filter(|file| file.condensed.is_some())
+        .count();
+    if condensed_count > 0 {
+        write!(stats, ", files_condensed={}", condensed_count)?;
+    }
+    if let Some(saved) = dump.tokens_stripped {
+        write!(stats, ", tokens_stripped={}", saved)?;
+    }
+    if let Some(model) = &args.model {
+        write_cost(&mut stats, token_count, model, config)?;
+    }
+    eprintln!("{}", stats);
+    Ok(())
+}
+
+/// Appends `model=..., input_cost=$...` to a stats line, warning if the model has no price.
+fn write_cost(stats: &mut String, tokens: usize, model: &str, config: &Config) -> Result<()> {
+    match pricing::input_cost(tokens, model, &config.prices) {
+        Some(cost) => write!(stats, ", model={}, input_cost=${:.4}", model, cost)?,
+        None => eprintln!(
+            "Warning: no price known for model {}; add one under [prices] in the config",
+            model
+        ),
+    }
+    Ok(())
+}
+
+fn write_manifest(dump: &Dump, path: &Path, tokenizer: &CoreBPE) -> Result<()> {
+    manifest::Manifest::new(
+        &dump.root_label,
+        dump.token_count(),
+        &dump.files,
+        &dump.skipped,
+        tokenizer,
+    )
+    .write(path)
+}
+
+/// A rendered dump, ready to be written out or sent to a model.
+struct Dump {
+    root_dir: PathBuf,
+    root_label: String,
+    files: Vec<FileDump>,
+    /// Paths of `files` before `--anonymize`, sorted, for `--write-filelist`.
+    selection: Vec<String>,
+    skipped: Vec<SkippedFile>,
+    redactions: Vec<redact::Redaction>,
+    /// Tokens removed from file contents by `--strip`.
+    tokens_stripped: Option<usize>,
+    /// Sections shared by every part.
+    context: PromptContext,
+    /// Whether the parts are rendered with part markers, with `--chunk-tokens`.
+    chunked: bool,
+    /// File range of each part (a single part unless chunked).
+    parts: Vec<Range<usize>>,
+    /// Tokens of each part, once the parts were written or rendered.
+    part_tokens: Vec<usize>,
+}
+
+impl Dump {
+    fn token_count(&self) -> usize {
+        self.part_tokens.iter().sum()
+    }
+
+    /// Renders part `idx` into `out`.
+    fn write_part(&self, idx: usize, out: &mut impl fmt::Write) -> Result<()> {
+        let part = self.chunked.then_some(chunk::Part {
+            index: idx + 1,
+            count: self.parts.len(),
+        });
+        write_prompt(
+            out,
+            &self.context,
+            &self.files[self.parts[idx].clone()],
+            part,
+        )
+    }
+
+    /// Streams part `idx` into `out`, returning its tokens if `tokenizer` is given.
+    fn stream_part(
+        &self,
+        idx: usize,
+        out: &mut dyn Write,
+        tokenizer: Option<&CoreBPE>,
+    ) -> Result<usize> {
+        let mut stream = stream::TokenStream::new(out, tokenizer);
+        let rendered = self.write_part(idx, &mut stream);
+        let tokens = stream.finish()?;
+        rendered?;
+        Ok(tokens)
+    }
+
+    /// A hash of each rendered part, to tell whether `--watch` has anything new to write
+    /// without keeping the previous prompt around.
+    fn fingerprints(&self) -> Result<Vec<u64>> {
+        (0..self.parts.len())
+            .map(|idx| {
+                let mut fingerprint = stream::Fingerprint::default();
+                self.stream_part(idx, &mut fingerprint, None)?;
+                Ok(fingerprint.finish())
+            })
+            .collect()
+    }
+}
+
+fn build_dump(args: &DumpArgs, task: &str, config: &Config, tokenizer: &CoreBPE) -> Result<Dump> {
+    let checkout = args.repo.as_deref().map(remote::fetch).transpose()?;
+    let compared = match args.compare.as_deref() {
+        Some([old, new]) => {
+            let (old, new) = (
+                resolve_root(Some(old.clone()))?,
+                resolve_root(Some(new.clone()))?,
+            );
+            if !old.is_dir() || !new.is_dir() {
+                bail!("--compare takes two directories");
+            }
+            Some((old, new))
+        }
+        _ => None,
+    };
+    let mut missing = Vec::new();
+    let (root_dir, inputs) = match (&checkout, &compared) {
+        (Some(checkout), _) => {
+            let root_dir = checkout.path().canonicalize()?;
+            (root_dir.clone(), vec![root_dir])
+        }
+        (None, Some((_, new))) => (new.clone(), vec![new.clone()]),
+        // Entries are read straight from the archive, so the root only names it.
+        (None, None) if let Some(archive) = &args.archive => {
+            (resolve_root(Some(archive.clone()))?, Vec::new())
+        }
+        (None, None) => match &args.from_filelist {
+            Some(list) => listed_inputs(list, args, &mut missing)?,
+            None => resolve_inputs(args.path.iter().chain(&args.paths))?,
+        },
+    };
+    let root_label = match &checkout {
+        Some(checkout) => checkout.label.clone(),
+        None if let Some(archive) = &args.archive => archive.display().to_string(),
+        None if args.anonymize.is_some() => ".".to_string(),
+        None => root_dir.display().to_string(),
+    };
+
+    let mut unchanged = Vec::new();
+    let Collection {
+        mut files,
+        mut skipped,
+        mut symlinks,
+        mut filtered,
+        mut sizes,
+    } = match &compared {
+        Some((old, new)) => {
+            let old = collect_files(old, &args.collect, config)?;
+            let new = collect_files(new, &args.collect, config)?;
+            let collection;
+            (collection, unchanged) = compare::merge(old, new);
+            collection
+        }
+        None if args.archive.is_some() => collect_archive(&root_dir, &args.collect, config)?,
+        None => collect_paths(&root_dir, &inputs, &args.collect, config)?,
+    };
+    if !missing.is_empty() {
+        skipped.extend(missing.into_iter().map(|relative_path| SkippedFile {
+            relative_path,
+            reason: SkipReason::Missing,
+            bytes: None,
+        }));
+        skipped.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    }
+
+    // Never feed a previous run's output back into the dump.
+    let own_outputs: Vec<PathBuf> = [
+        &args.output,
+        &args.chunk_dir,
+        &args.manifest,
+        &args.write_filelist,
+        &args.anonymize,
+        &args.export.as_ref().map(|export| export.path.clone()),
+    ]
+    .into_iter()
+    .flatten()
+    .filter_map(|path| path.canonicalize().ok())
+    .collect();
+    files.retain(|file| {
+        let path = root_dir.join(&file.relative_path);
+        !own_outputs.iter().any(|output| path.starts_with(output))
+    });
+
+    if !args.workspace_member.is_empty() {
+        let selected = workspace::select(&files, &args.workspace_member)?;
+        let (kept, dropped) = files
+            .into_iter()
+            .partition(|file| selected.contains(&file.relative_path));
+        files = kept;
+        filtered.extend(dropped.into_iter().map(|file: FileDump| file.relative_path));
+    }
+
+    if !args.around.is_empty() {
+        let seeds = args
+            .around
+            .iter()
+            .map(|path| seed_path(&root_dir, path, &files))
+            .collect::<Result<Vec<_>>>()?;
+        let selected = deps::around(&files, &seeds, args.hops);
+        let (kept, dropped) = files
+            .into_iter()
+            .partition(|file| selected.contains(&file.relative_path));
+        files = kept;
+        filtered.extend(dropped.into_iter().map(|file: FileDump| file.relative_path));
+    }
+
+    if !args.grep.is_empty() {
+        let (kept, dropped) = files
+            .into_iter()
+            .partition(|file| grep::matches(&file.contents, &args.grep));
+        files = kept;
+        filtered.extend(dropped.into_iter().map(|file: FileDump| file.relative_path));
+    }
+
+    if !args.full_lockfiles {
+        files.par_iter_mut().for_each(|file| {
+            let path = root_dir.join(&file.relative_path);
+            let dir = path.parent().unwrap_or(&root_dir);
+            if let Some((kind, summary)) =
+                lockfiles::condense(dir, &file.relative_path, &file.contents)
+            {
+                file.condensed = Some((kind, file.contents.len()));
+                file.contents = summary;
+                file.source = None;
+                file.language = None;
+            }
+        });
+    }
+
+    if args.file_info {
+        let paths = files
+            .iter()
+            .map(|file| file.relative_path.as_str())
+            .collect();
+        let commits = git::last_commits(&root_dir, &paths)?;
+        for file in &mut files {
+            let last_commit = commits
+                .as_ref()
+                .map(|commits| commits.get(&file.relative_path));
+            file.info = Some(file_info(file, last_commit));
+        }
+    }
+
+    let mut redactions = Vec::new();
+    if !args.no_redact {
+        for file in &mut files {
+            if let Some((contents, found)) = redact::redact(&file.relative_path, &file.contents) {
+                file.contents = contents;
+                file.source = None;
+                redactions.extend(found);
+            }
+        }
+    }
+
+    // After redaction, which needs whole files: an excerpt may cut a private key block after
+    // its BEGIN line, and the rest would no longer be recognized.
+    if let Some(context) = args.context_lines {
+        for file in files.iter_mut().filter(|file| file.condensed.is_none()) {
+            file.contents = grep::excerpt(&file.contents, &args.grep, context);
+            file.source = None;
+        }
+    }
+
+    let mut tokens_stripped = None;
+    if let Some(mode) = args.strip {
+        let before: usize = file_tokens(tokenizer, &files).iter().sum();
+        files.par_iter_mut().for_each(|file| {
+            file.contents = strip::strip(&file.contents, file.language.as_deref(), mode);
+            file.source = None;
+        });
+        let after: usize = file_tokens(tokenizer, &files).iter().sum();
+        tokens_stripped = Some(before.saturating_sub(after));
+    }
+
+    // Anonymized path to original path, for `--write-filelist`.
+    let mut original_paths = HashMap::new();
+    if let Some(map_path) = &args.anonymize {
+        let originals: Vec<String> = files
+            .iter()
+            .map(|file| file.relative_path.clone())
+            .collect();
+        let all_paths = files
+            .iter()
+            .map(|file| file.relative_path.as_str())
+            .chain(skipped.iter().map(|file| file.relative_path.as_str()))
+            .chain(symlinks.iter().map(|link| link.relative_path.as_str()))
+            .chain(filtered.iter().map(String::as_str));
+        let mut anonymizer = anonymize::Anonymizer::new(all_paths, args.anonymize_strings);
+        for file in &mut files {
+            file.contents = anonymizer.contents(&file.contents);
+            file.source = None;
+            file.relative_path = anonymizer.path(&file.relative_path);
+        }
+        original_paths = files
+            .iter()
+            .map(|file| file.relative_path.clone())
+            .zip(originals.iter().cloned())
+            .collect();
+        for file in &mut skipped {
+            file.relative_path = anonymizer.path(&file.relative_path);
+        }
+        for link in &mut symlinks {
+            link.relative_path = anonymizer.path(&link.relative_path);
+            link.target = "[anonymized]".to_string();
+        }
+        for path in &mut filtered {
+            let anonymized = anonymizer.path(path);
+            // Placeholders name no file on disk, so sizes are looked up before renaming.
+            if args.summarize_excluded
+                && let Some(size) = sizes.get(path.as_str()).copied().or_else(|| {
+                    fs::metadata(root_dir.join(path.as_str()))
+                        .ok()
+                        .map(|meta| meta.len())
+                })
+            {
+                sizes.insert(anonymized.clone(), size);
+            }
+            *path = anonymized;
+        }
+        files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+        anonymizer.write_map(map_path, originals.iter().map(String::as_str))?;
+    }
+
+    if let Some(threshold) = args.summarize_over {
+        let Some(model) = args.summary_model.as_deref().or(args.model.as_deref()) else {
+            bail!("--summarize-over requires --summary-model or --model");
+        };
+        let counts = file_tokens(tokenizer, &files);
+        files
+            .par_iter_mut()
+            .zip(counts)
+            .filter(|(_, tokens)| *tokens > threshold)
+            .try_for_each(|(file, tokens)| -> Result<()> {
+                file.contents = summarize::summarize(
+                    model,
+                    &file.relative_path,
+                    &file.contents,
+                    file.language.as_deref(),
+                )?;
+                file.source = None;
+                file.language = None;
+                file.summarized_from = Some(tokens);
+                Ok(())
+            })?;
+    }
+
+    if args.file_tokens {
+        let counts = file_tokens(tokenizer, &files);
+        for (file, tokens) in files.iter_mut().zip(counts) {
+            file.tokens = Some(tokens);
+        }
+    }
+
+    let git_info = if args.git_info {
+        Some(git::describe(&root_dir, args.git_log)?)
+    } else {
+        None
+    };
+    let env_info = if args.env_info {
+        Some(environment::describe(&root_dir)?)
+    } else {
+        None
+    };
+    let repo_summary = if args.repo_summary {
+        Some(summary::describe(&files, &file_tokens(tokenizer, &files))?)
+    } else {
+        None
+    };
+    let context = PromptContext {
+        root: root_label.clone(),
+        task: task.to_string(),
+        repo_summary,
+        env_info,
+        git_info,
+        symlinks,
+        omitted: Vec::new(),
+        compared_with: compared.as_ref().map(|(old, _)| old.display().to_string()),
+        unchanged,
+        tree_tokens: None,
+        snippets: args
+            .snippet
+            .iter()
+            .map(|name| snippets::read(name))
+            .collect::<Result<_>>()?,
+        response_format: args.response_format,
+    };
+
+    rank::sort(&mut files, args.order, task);
+    if !args.no_dedup {
+        dedup(&mut files);
+    }
+
+    if let Some(limit) = args.max_tokens {
+        let mut used = prompt_overhead(tokenizer, &context, &[None])?;
+        let costs = file_costs(tokenizer, &files, args.tree_tokens)?;
+        let keep = costs
+            .iter()
+            .take_while(|&&cost| {
+                used += cost;
+                used <= limit
+            })
+            .count();
+        skipped.extend(files.drain(keep..).map(|file| SkippedFile {
+            bytes: Some(file.contents.len() as u64),
+            relative_path: file.relative_path,
+            reason: SkipReason::OverBudget,
+        }));
+        skipped.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    }
+
+    let omitted = if args.summarize_excluded {
+        omitted_files(&root_dir, &skipped, &filtered, &sizes)
+    } else {
+        Vec::new()
+    };
+    let tree_tokens = args.tree_tokens.then(|| {
+        let counts = file_tokens(tokenizer, &files);
+        let total = counts.iter().sum();
+        tree::TreeTokens {
+            tokens: files
+                .iter()
+                .map(|file| file.relative_path.clone())
+                .zip(counts)
+                .collect(),
+            budget: args.max_tokens.unwrap_or(total),
+        }
+    });
+    let context = PromptContext {
+        omitted,
+        tree_tokens,
+        ..context
+    };
+
+    let chunks = match args.chunk_tokens {
+        Some(limit) => plan_chunks(tokenizer, &context, &files, limit)?,
+        None => std::iter::once(0..files.len()).collect(),
+    };
+    let mut selection: Vec<String> = files
+        .iter()
+        .map(|file| {
+            original_paths
+                .remove(&file.relative_path)
+                .unwrap_or_else(|| file.relative_path.clone())
+        })
+        .collect();
+    selection.sort();
+
+    Ok(Dump {
+        root_dir,
+        root_label,
+        files,
+        selection,
+        skipped,
+        redactions,
+        tokens_stripped,
+        context,
+        chunked: args.chunk_tokens.is_some(),
+        parts: chunks,
+        part_tokens: Vec::new(),
+    })
+}
+
+/// Rough tokens of a `--tree-tokens` annotation, ` (~1.2k tokens) [#.........] 4%`.
+const TREE_ANNOTATION_TOKENS: usize = 16;
+
+/// Files shorter than this are not worth replacing with a reference to another file.
+const MIN_DEDUP_BYTES: usize = 256;
+
+/// Empties files whose contents, up to line endings and trailing whitespace, repeat an earlier
+/// file's, and points them to that file. Running after ordering keeps each original ahead of
+/// its duplicates, so a budget never drops the original but keeps a duplicate.
+fn dedup(files: &mut [FileDump]) {
+    let mut seen: HashMap<String, String> = HashMap::new();
+    for file in files {
+        if file.contents.len() < MIN_DEDUP_BYTES
+            || file.summarized_from.is_some()
+            || file.condensed.is_some()
+        {
+            continue;
+        }
+        let normalized = file
+            .contents
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n");
+        match seen.get(normalized.trim_end()) {
+            Some(original) => {
+                file.duplicate_of = Some(original.clone());
+                file.contents.clear();
+                file.source = None;
+                file.language = None;
+                if file.tokens.is_some() {
+                    file.tokens = Some(0);
+                }
+            }
+            None => {
+                seen.insert(
+                    normalized.trim_end().to_string(),
+                    file.relative_path.clone(),
+                );
+            }
+        }
+    }
+}
+
+/// The relative path of an `--around` file, given relative to the working directory or the root.
+fn seed_path(root: &Path, path: &Path, files: &[FileDump]) -> Result<String> {
+    let relative = match path.canonicalize() {
+        Ok(absolute) if absolute.starts_with(root) => to_relative(root, &absolute),
+        Ok(_) => bail!("--around {} is outside {}", path.display(), root.display()),
+        Err(_) => path.display().to_string(),
+    };
+    match files
+        .iter()
+        .find(|file| Path::new(&file.relative_path) == Path::new(&relative))
+    {
+        Some(file) => Ok(file.relative_path.clone()),
+        None => bail!(
+            "--around {} is not among the collected files",
+            path.display()
+        ),
+    }
+}
+
+/// Skipped and filtered files with their sizes on disk or in the archive, sorted by path.
+fn omitted_files(
+    root: &Path,
+    skipped: &[SkippedFile],
+    filtered: &[String],
+    sizes: &HashMap<String, u64>,
+) -> Vec<OmittedFile> {
+    let paths = skipped
+        .iter()
+        .filter(|file| !matches!(file.reason, SkipReason::SymlinkLoop(_)))
+        .map(|file| (&file.relative_path, file.bytes))
+        .chain(filtered.iter().map(|path| (path, None)))
+        .filter(|(path, _)| path.as_str() != "<walker>");
+    let mut omitted: Vec<OmittedFile> = paths
+        .map(|(path, bytes)| OmittedFile {
+            relative_path: path.clone(),
+            bytes: bytes
+                .or_else(|| sizes.get(path).copied())
+                .or_else(|| fs::metadata(root.join(path)).ok().map(|meta| meta.len()))
+                .unwrap_or(0),
+        })
+        .collect();
+    omitted.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    omitted
+}
+
+/// Splits `files` into ranges whose rendered parts should fit in `limit` tokens.
+fn plan_chunks(
+    tokenizer: &CoreBPE,
+    context: &PromptContext,
+    files: &[FileDump],
+    limit: usize,
+) -> Result<Vec<Range<usize>>> {
+    let parts = [(1, 2), (2, 2)].map(|(index, count)| Some(chunk::Part { index, count }));
+    let overhead = prompt_overhead(tokenizer, context, &parts)?;
+    let budget = limit.checked_sub(overhead).filter(|&budget| budget > 0).with_context(|| {
+        format!("--chunk-tokens {limit} leaves no room for files (prompt overhead is {overhead} tokens)")
+    })?;
+
+    let annotated = context.tree_tokens.is_some();
+    Ok(chunk::plan(
+        &file_costs(tokenizer, files, annotated)?,
+        budget,
+    ))
+}
+
+/// Tokens of a prompt without any files, taking the largest over the given part markers.
+fn prompt_overhead(
+    tokenizer: &CoreBPE,
+    context: &PromptContext,
+    parts: &[Option<chunk::Part>],
+) -> Result<usize> {
+    let mut overhead = 0;
+    for &part in parts {
+        let empty = render_prompt(context, &[], part)?;
+        overhead = overhead.max(tokenizer.encode_ordinary(&empty).len());
+    }
+    Ok(overhead)
+}
+
+/// Tokens of each file's contents.
+fn file_tokens(tokenizer: &CoreBPE, files: &[FileDump]) -> Vec<usize> {
+    progress::start(progress::Phase::Counting(files.len()));
+    let tokens = files
+        .par_iter()
+        .map(|file| {
+            let tokens = cache::count(tokenizer, file);
+            progress::counted();
+            tokens
+        })
+        .collect();
+    progress::finish();
+    tokens
+}
+
+/// Tokens a file adds to the prompt: its section, and its file tree line (annotated with
+/// `--tree-tokens` if `annotated`).
+fn file_costs(tokenizer: &CoreBPE, files: &[FileDump], annotated: bool) -> Result<Vec<usize>> {
+    let tree_line = if annotated { TREE_ANNOTATION_TOKENS } else { 0 } + 2;
+    files
+        .par_iter()
+        .map(|file| {
+            let mut section = String::new();
+            render_file(&mut section, file)?;
+            let framing = section.replacen(&file.contents, "", 1);
+            Ok(cache::count(tokenizer, file)
+                + count_tokens(tokenizer, &framing)
+                + tokenizer.encode_ordinary(&file.relative_path).len()
+                + tree_line)
+        })
+        .collect()
+}
+
+fn part_delimiter(index: usize, count: usize) -> String {
+    format!("-----8<----- part {index} of {count} -----8<-----")
+}
+
+/// Sections shared by every part of a dump.
+struct PromptContext {
+    root: String,
+    task: String,
+    repo_summary: Option<String>,
+    env_info: Option<String>,
+    git_info: Option<String>,
+    symlinks: Vec<Symlink>,
+    omitted: Vec<OmittedFile>,
+    /// The old version's root, with `--compare`; `root` is then the new one's.
+    compared_with: Option<String>,
+    /// Files that are the same in both versions, with `--compare`; listed only in the tree.
+    unchanged: Vec<String>,
+    /// Token counts for the file tree's annotations, with `--tree-tokens`.
+    tree_tokens: Option<tree::TreeTokens>,
+    /// Texts of the `--snippet`s, in the order given.
+    snippets: Vec<String>,
+    response_format: Option<response::ResponseFormat>,
+}
+
+fn render_prompt(
+    context: &PromptContext,
+    files: &[FileDump],
+    part: Option<chunk::Part>,
+) -> Result<String> {
+    let mut prompt = String::new();
+    write_prompt(&mut prompt, context, files, part)?;
+    Ok(prompt)
+}
+
+/// Renders a prompt (or one part of a chunked one) section by section into `prompt`.
+fn write_prompt(
+    prompt: &mut impl fmt::Write,
+    context: &PromptContext,
+    files: &[FileDump],
+    part: Option<chunk::Part>,
+) -> Result<()> {
+    let subject = match context.compared_with {
+        Some(_) => "a comparison of two versions of a directory",
+        None => "the context of a directory",
+    };
+    match part {
+        Some(part) => writeln!(
+            prompt,
+            "The following is part {} of {} of {subject}. After the last part, I will give you a task. You need to do the task based on the context.",
+            part.index, part.count
+        )?,
+        None => writeln!(
+            prompt,
+            "The following is {subject}. After the context, I will give you a task. You need to do the task based on the context."
+        )?,
+    }
+    writeln!(prompt)?;
+    writeln!(prompt, "# Repository Context")?;
+    match &context.compared_with {
+        Some(old) => {
+            writeln!(prompt, "Old version: {old}")?;
+            writeln!(prompt, "New version: {}", context.root)?;
+        }
+        None => writeln!(prompt, "Root: {}", context.root)?,
+    }
+    writeln!(prompt)?;
+
+    if let Some(repo_summary) = &context.repo_summary {
+        write!(prompt, "{}", repo_summary)?;
+    }
+
+    if let Some(env_info) = &context.env_info {
+        writeln!(prompt, "{}", env_info)?;
+    }
+
+    if let Some(git_info) = &context.git_info {
+        writeln!(prompt, "{}", git_info)?;
+    }
+
+    if context.compared_with.is_some() {
+        render_changes(prompt, files, context.unchanged.len())?;
+    }
+
+    let file_tree = tree::file_tree(
+        files,
+        &context.symlinks,
+        &context.omitted,
+        &context.unchanged,
+        context.tree_tokens.as_ref(),
+    );
+    writeln!(prompt, "## File Tree")?;
+    writeln!(prompt, "{}", file_tree)?;
+    writeln!(prompt)?;
+
+    writeln!(prompt, "## Files")?;
+    for file in files {
+        render_file(prompt, file)?;
+    }
+
+    if let Some(part) = part.filter(|part| part.index < part.count) {
+        writeln!(
+            prompt,
+            "This is the end of part {} of {}. Do not start the task yet; reply only with \"Received part {} of {}\" and wait for the next part.",
+            part.index, part.count, part.index, part.count
+        )?;
+        return Ok(());
+    }
+
+    if !context.snippets.is_empty() {
+        writeln!(prompt, "# Instructions")?;
+        for snippet in &context.snippets {
+            writeln!(prompt, "{}", snippet.trim())?;
+            writeln!(prompt)?;
+        }
+    }
+
+    writeln!(prompt, "# Task")?;
+    writeln!(
+        prompt,
+        "Based on the context above, please finish the following task:"
+    )?;
+    writeln!(prompt, "{}", context.task.trim_end())?;
+    writeln!(prompt)?;
+
+    if let Some(format) = context.response_format {
+        writeln!(prompt, "# Response Format")?;
+        writeln!(prompt, "{}", format.instructions())?;
+        writeln!(prompt)?;
+    }
+
+    Ok(())
+}
+
+/// Lists the added, removed and modified files of a `--compare` dump.
+fn render_changes(
+    prompt: &mut impl fmt::Write,
+    files: &[FileDump],
+    unchanged: usize,
+) -> Result<()> {
+    writeln!(prompt, "## Changes")?;
+    writeln!(
+        prompt,
+        "Modified files are shown as unified diffs from the old version to the new one, and added and removed files with their full contents. Files not marked in the file tree are the same in both versions."
+    )?;
+    writeln!(prompt)?;
+    for change in [
+        compare::Change::Added,
+        compare::Change::Removed,
+        compare::Change::Modified,
+    ] {
+        let paths: Vec<&str> = files
+            .iter()
+            .filter(|file| file.change == Some(change))
+            .map(|file| file.relative_path.as_str())
+            .collect();
+        let label = change.label();
+        let label = label[..1].to_uppercase() + &label[1..];
+        if paths.is_empty() {
+            writeln!(prompt, "{label}: none")?;
+        } else {
+            writeln!(prompt, "{label} ({}): {}", paths.len(), paths.join(", "))?;
+        }
+    }
+    writeln!(prompt, "Unchanged: {} files", with_thousands(unchanged))?;
+    writeln!(prompt)?;
+    Ok(())
+}
+
+fn render_file(prompt: &mut impl fmt::Write, file: &FileDump) -> Result<()> {
+    let fence = fence_for(&file.contents);
+    match file.tokens {
+        Some(tokens) => writeln!(
+            prompt,
+            "### {} ({} tokens)",
+            &file.relative_path,
+            with_thousands(tokens)
+        )?,
+        None => writeln!(prompt, "### {}", &file.relative_path)?,
+    }
+    if let Some(info) = &file.info {
+        writeln!(prompt, "{}", info)?;
+    }
+    match file.change {
+        Some(compare::Change::Added) => writeln!(prompt, "[Added in the new version]")?,
+        Some(compare::Change::Removed) => writeln!(
+            prompt,
+            "[Removed in the new version; contents of the old version]"
+        )?,
+        Some(compare::Change::Modified) => writeln!(
+            prompt,
+            "[Modified; unified diff from the old version to the new one]"
+        )?,
+        None => {}
+    }
+    if let Some(tokens) = file.summarized_from {
+        writeln!(
+            prompt,
+            "[Summary generated by a model in place of the {}-token file; not its contents]",
+            with_thousands(tokens)
+        )?;
+    }
+    if let Some((kind, bytes)) = file.condensed {
+        writeln!(
+            prompt,
+            "[Summary of {} of {} bytes, in place of its contents]",
+            kind.description(),
+            with_thousands(bytes)
+        )?;
+    }
+    if let Some(original) = &file.duplicate_of {
+        writeln!(
+            prompt,
+            "[Same contents as {original} (up to whitespace); not repeated]\n"
+        )?;
+        return Ok(());
+    }
+    writeln!(
+        prompt,
+        "{}{}",
+        fence,
+        file.language.as_deref().unwrap_or("")
+    )?;
+    prompt.write_str(&file.contents)?;
+    if !file.contents.ends_with('\n') {
+        prompt.write_char('\n')?;
+    }
+    prompt.write_str(&fence)?;
+    prompt.write_str("\n\n")?;
+    Ok(())
+}
+
+/// `12,345 bytes, 420 lines, modified 2026-01-31 09:30 UTC, last commit a1b2c3d (2026-01-30) Fix parser`
+///
+/// `last_commit` is `None` outside a Git work tree, and `Some(None)` for untracked files.
+fn file_info(file: &FileDump, last_commit: Option<Option<&String>>) -> String {
+    let mut info = format!(
+        "{} bytes, {} lines",
+        with_thousands(file.contents.len()),
+        with_thousands(file.contents.lines().count())
+    );
+    if let Some(secs) = file
+        .modified
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|elapsed| elapsed.as_secs())
+    {
+        info.push_str(&format!(", modified {}", utc_timestamp(secs)));
+    }
+    match last_commit {
+        Some(Some(commit)) => info.push_str(&format!(", last commit {commit}")),
+        Some(None) => info.push_str(", not committed"),
+        None => {}
+    }
+    info
+}
+
+/// `2026-01-31 09:30 UTC` for seconds since the Unix epoch.
+fn utc_timestamp(secs: u64) -> String {
+    // Civil date from day count, after Howard Hinnant's `civil_from_days`.
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02} UTC",
+        secs % 86_400 / 3_600,
+        secs % 3_600 / 60
+    )
 }
 
-enum SkipReason {
-    TooLarge(u64),
-    NonUtf8,
-    Io(String),
+/// Returns a backtick fence longer than any backtick run in `contents`, so the contents
+/// can never close the code block early.
+fn fence_for(contents: &str) -> String {
+    let longest_run = contents
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    "`".repeat(longest_run.max(2) + 1)
 }
 
-impl fmt::Display for SkipReason {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            SkipReason::TooLarge(len) => write!(f, "exceeds size limit ({} bytes)", len),
-            SkipReason::NonUtf8 => write!(f, "non-UTF-8 content"),
-            SkipReason::Io(err) => write!(f, "I/O error: {err}"),
-        }
+fn run_stats(mut args: StatsArgs, config: &Config) -> Result<()> {
+    let root_dir = resolve_root(args.path)?;
+    args.collect.counts_only = !args.repo_summary;
+    let Collection { files, skipped, .. } = collect_files(&root_dir, &args.collect, config)?;
+
+    let tokenizer = o200k_base().context("failed to load o200k_base tokenizer")?;
+    let breakdown = stats::Breakdown::new(&files, &tokenizer, args.depth);
+
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    stdout.write_all(breakdown.render(args.sort, args.top).as_bytes())?;
+    if let Some(model) = &args.model {
+        let mut line = format!("Cost: tokens={}", breakdown.total_tokens());
+        write_cost(&mut line, breakdown.total_tokens(), model, config)?;
+        writeln!(stdout, "{}", line)?;
     }
+    if args.repo_summary {
+        let tokens: Vec<usize> = files
+            .par_iter()
+            .map(|file| cache::count(&tokenizer, file))
+            .collect();
+        writeln!(stdout)?;
+        stdout.write_all(summary::describe(&files, &tokens)?.as_bytes())?;
+    }
+    stdout.flush()?;
+
+    for skipped_file in &skipped {
+        eprintln!(
+            "Skipped: path={}, reason={}",
+            skipped_file.relative_path, skipped_file.reason
+        );
+    }
+    eprintln!("tokenizer: {}", TOKENIZER_NAME);
+
+    Ok(())
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+fn run_tree(args: TreeArgs, config: &Config) -> Result<()> {
+    let root_dir = resolve_root(args.path)?;
+    let collection = collect_files(&root_dir, &args.collect, config)?;
 
-    match cli.command {
-        Command::Dump(args) => run_dump(args)?,
-        Command::Count(args) => run_count(args)?,
+    let mut tree = tree::Tree::default();
+    let tokenizer = (!args.no_sizes)
+        .then(o200k_base)
+        .transpose()
+        .context("failed to load o200k_base tokenizer")?;
+    let tokens: Vec<usize> = match &tokenizer {
+        Some(tokenizer) => collection
+            .files
+            .par_iter()
+            .map(|file| cache::count(tokenizer, file))
+            .collect(),
+        None => vec![0; collection.files.len()],
+    };
+    for (file, tokens) in collection.files.iter().zip(tokens) {
+        let size = tree::Size {
+            files: 1,
+            tokens,
+            bytes: file.contents.len(),
+        };
+        tree.insert(&file.relative_path, tree::Leaf::File, size);
+    }
+    for link in &collection.symlinks {
+        let leaf = tree::Leaf::Symlink(link.target.clone());
+        tree.insert(&link.relative_path, leaf, tree::Size::default());
     }
 
+    let options = tree::RenderOptions {
+        max_depth: args.max_depth,
+        sizes: !args.no_sizes,
+        budget: None,
+    };
+    println!("{}", tree.render(options));
+
+    for skipped_file in &collection.skipped {
+        eprintln!(
+            "Skipped: path={}, reason={}",
+            skipped_file.relative_path, skipped_file.reason
+        );
+    }
+    if tokenizer.is_some() {
+        eprintln!("tokenizer: {}", TOKENIZER_NAME);
+    }
     Ok(())
 }
 
-fn run_dump(args: DumpArgs) -> Result<()> {
-    let user_message = args.task;
-    let root_dir = match args.path {
-        Some(path) => path,
-        None => env::current_dir().context("failed to determine current directory")?,
+fn run_render(args: RenderArgs, config: &Config) -> Result<()> {
+    let template = if args.template.as_os_str() == "-" {
+        io::read_to_string(io::stdin()).context("failed to read template from stdin")?
+    } else {
+        fs::read_to_string(&args.template)
+            .with_context(|| format!("failed to read template {}", args.template.display()))?
     };
+    let vars = args.vars.iter().cloned().collect();
 
-    let root_dir = root_dir
-        .canonicalize()
-        .with_context(|| format!("failed to resolve path {}", root_dir.display()))?;
+    let mut embedded = Vec::new();
+    let mut redactions = Vec::new();
+    let mut embed_file = |path: &Path| -> Result<String> {
+        let mut file = read_template_file(path, config)?;
+        if !args.no_redact
+            && let Some((contents, found)) = redact::redact(&file.relative_path, &file.contents)
+        {
+            file.contents = contents;
+            redactions.extend(found);
+        }
+        let mut section = String::new();
+        render_file(&mut section, &file)?;
+        embedded.push(file.relative_path);
+        Ok(section)
+    };
+    let prompt = template::expand(&template, &vars, |embed| match embed {
+        template::Embed::File(path) => embed_file(Path::new(path)),
+        template::Embed::Files => args
+            .files
+            .iter()
+            .map(|path| embed_file(path))
+            .collect::<Result<String>>(),
+    })?;
 
-    let (files, skipped) = collect_files(&root_dir, args.max_file_size)?;
+    match &args.output {
+        Some(path) => fs::write(path, &prompt)
+            .with_context(|| format!("failed to write {}", path.display()))?,
+        None => io::stdout().lock().write_all(prompt.as_bytes())?,
+    }
 
-    let mut prompt = String::new();
+    for redaction in &redactions {
+        eprintln!("Redacted: {}", redaction);
+    }
+    let tokenizer = o200k_base().context("failed to load o200k_base tokenizer")?;
+    eprintln!(
+        "Stats: tokens={}, files_included={}, bytes={}",
+        count_tokens(&tokenizer, &prompt),
+        embedded.len(),
+        prompt.len()
+    );
+    Ok(())
+}
 
-    writeln!(
-        prompt,
-        "The following is the context of a directory. After the context, I will give you a task. You need to do the task based on the context."
-    )?;
-    writeln!(prompt)?;
-    writeln!(prompt, "# Repository Context")?;
-    writeln!(prompt, "Root: {}", root_dir.display())?;
-    writeln!(prompt)?;
+/// Reads a file named on the `render` command line, decoded and with its language detected.
+fn read_template_file(path: &Path, config: &Config) -> Result<FileDump> {
+    let data = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let Some((contents, encoding)) = decode::decode(data, false) else {
+        bail!("{} is binary or in an undetected encoding", path.display());
+    };
+    let relative_path = slashed(path);
+    let language = lang::detect(&relative_path, &contents, &config.languages);
+    Ok(FileDump {
+        relative_path,
+        contents,
+        language,
+        truncated_from: None,
+        encoding,
+        tokens: None,
+        modified: None,
+        info: None,
+        summarized_from: None,
+        duplicate_of: None,
+        change: None,
+        condensed: None,
+        source: None,
+    })
+}
 
-    let file_tree = build_file_tree(&files);
-    writeln!(prompt, "## File Tree")?;
-    writeln!(prompt, "{}", file_tree)?;
-    writeln!(prompt)?;
+fn run_diffsplit(args: DiffsplitArgs) -> Result<()> {
+    let diff = match &args.input {
+        Some(path) if path.as_os_str() != "-" => fs::read_to_string(path)
+            .with_context(|| format!("failed to read diff {}", path.display()))?,
+        _ => io::read_to_string(io::stdin()).context("failed to read diff from stdin")?,
+    };
+    let tokenizer = o200k_base().context("failed to load o200k_base tokenizer")?;
 
-    writeln!(prompt, "## Files")?;
-    for file in &files {
-        writeln!(prompt, "### {}", &file.relative_path)?;
-        prompt.push_str("```\n");
-        prompt.push_str(&file.contents);
-        if !file.contents.ends_with('\n') {
-            prompt.push('\n');
+    let (pieces, groups) = match args.chunk_tokens {
+        Some(limit) => {
+            let empty = diffsplit::render(&[], &args.task, chunk::Part { index: 1, count: 2 })?;
+            let overhead = count_tokens(&tokenizer, &empty);
+            let budget = limit.checked_sub(overhead).filter(|&budget| budget > 0).with_context(|| {
+                format!("--chunk-tokens {limit} leaves no room for diffs (prompt overhead is {overhead} tokens)")
+            })?;
+            // Each piece also costs its entry in the file list, heading and fence.
+            let cost = |text: &str| count_tokens(&tokenizer, text) + 16;
+            let pieces = diffsplit::split_bounded(&diff, budget, cost);
+            let costs: Vec<usize> = pieces
+                .iter()
+                .map(|piece| cost(&piece.text) + 2 * count_tokens(&tokenizer, &piece.path))
+                .collect();
+            let groups = chunk::plan(&costs, budget);
+            (pieces, groups)
+        }
+        None => {
+            let pieces = diffsplit::split_files(&diff);
+            let groups = (0..pieces.len()).map(|idx| idx..idx + 1).collect();
+            (pieces, groups)
         }
-        prompt.push_str("```\n\n");
+    };
+    if pieces.is_empty() {
+        bail!("no file diffs found in the input");
     }
 
-    writeln!(prompt, "# Task")?;
-    writeln!(
-        prompt,
-        "Based on the context above, please finish the following task:"
+    let count = groups.len();
+    let prompts = groups
+        .iter()
+        .enumerate()
+        .map(|(idx, range)| {
+            let part = chunk::Part {
+                index: idx + 1,
+                count,
+            };
+            diffsplit::render(&pieces[range.clone()], &args.task, part)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let part_paths = write_parts(
+        prompts.len(),
+        args.chunk_dir.as_deref(),
+        None,
+        |idx, out| Ok(out.write_all(prompts[idx].as_bytes())?),
     )?;
-    writeln!(prompt, "{}", user_message.trim_end())?;
-    writeln!(prompt)?;
-
-    let mut stdout = io::BufWriter::new(io::stdout().lock());
-    stdout.write_all(prompt.as_bytes())?;
-    stdout.flush()?;
-
-    // Stats info (stderr)
 
-    let tokenizer = o200k_base().context("failed to load o200k_base tokenizer")?;
-    let token_count = tokenizer.encode_ordinary(&prompt).len();
-    let included_count = files.len();
-    let skipped_count = skipped.len();
-    let total_bytes: usize = files.iter().map(|file| file.contents.len()).sum();
-
-    if !skipped.is_empty() {
-        for skipped_file in &skipped {
-            eprintln!(
-                "Skipped: path={}, reason={}",
-                skipped_file.relative_path, skipped_file.reason
-            );
+    let mut token_count = 0;
+    for (idx, (range, prompt)) in groups.iter().zip(&prompts).enumerate() {
+        let tokens = count_tokens(&tokenizer, prompt);
+        token_count += tokens;
+        let mut line = format!(
+            "Part: index={}/{}, tokens={}, pieces={}",
+            idx + 1,
+            count,
+            tokens,
+            range.len()
+        );
+        if let Some(path) = part_paths.get(idx) {
+            write!(line, ", path={}", path.display())?;
         }
+        if args.chunk_tokens.is_some_and(|limit| tokens > limit) {
+            line.push_str(", over_limit=true");
+        }
+        eprintln!("{}", line);
     }
-
     eprintln!(
-        "Stats: tokens={}, files_included={}, files_skipped={}, bytes={}",
-        token_count, included_count, skipped_count, total_bytes
+        "Stats: tokens={}, parts={}, pieces={}",
+        token_count,
+        count,
+        pieces.len()
     );
+
     Ok(())
 }
 
+fn run_mcp(args: McpArgs, config: Config) -> Result<()> {
+    let root_dir = resolve_root(args.path)?;
+    let tokenizer = o200k_base().context("failed to load o200k_base tokenizer")?;
+    mcp::Server::new(root_dir, config, tokenizer).run()
+}
+
 fn run_count(args: CountArgs) -> Result<()> {
     let tokenizer = o200k_base().context("failed to load o200k_base tokenizer")?;
 
@@ -185,7 +2592,7 @@ fn run_count(args: CountArgs) -> Result<()> {
                 .with_context(|| format!("failed to read file: {}", file_path.display()))?
         };
 
-        let tokens = tokenizer.encode_ordinary(&content).len();
+        let tokens = count_tokens(&tokenizer, &content);
         total_tokens += tokens;
 
         if multiple_files {
@@ -204,78 +2611,189 @@ fn run_count(args: CountArgs) -> Result<()> {
     Ok(())
 }
 
-fn collect_files(root: &Path, max_file_size: usize) -> Result<(Vec<FileDump>, Vec<SkippedFile>)> {
-    let mut builder = WalkBuilder::new(root);
+/// Counts tokens of `text`, tokenizing pieces in parallel.
+///
+/// `text` is split right after a blank line that precedes a `#` heading (file headers,
+/// section titles). BPE pre-tokenization always breaks there, so the total matches
+/// tokenizing `text` in one go.
+fn count_tokens(tokenizer: &CoreBPE, text: &str) -> usize {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (idx, _) in text.match_indices("\n\n#") {
+        pieces.push(&text[start..idx + 2]);
+        start = idx + 2;
+    }
+    pieces.push(&text[start..]);
+
+    pieces
+        .par_iter()
+        .map(|piece| tokenizer.encode_ordinary(piece).len())
+        .sum()
+}
+
+fn resolve_root(path: Option<PathBuf>) -> Result<PathBuf> {
+    let root_dir = match path {
+        Some(path) => path,
+        None => env::current_dir().context("failed to determine current directory")?,
+    };
+
+    root_dir
+        .canonicalize()
+        .with_context(|| format!("failed to resolve path {}", root_dir.display()))
+}
+
+/// Canonicalizes the paths given to `dump` (the working directory if none) and picks the
+/// root they are shown relative to: the directory itself if there is only one, otherwise the
+/// closest directory containing all of them.
+fn resolve_inputs<'a>(paths: impl Iterator<Item = &'a PathBuf>) -> Result<(PathBuf, Vec<PathBuf>)> {
+    let mut inputs = Vec::new();
+    for path in paths {
+        let resolved = path
+            .canonicalize()
+            .with_context(|| format!("failed to resolve path {}", path.display()))?;
+        if !inputs.contains(&resolved) {
+            inputs.push(resolved);
+        }
+    }
+    if inputs.is_empty() {
+        inputs.push(resolve_root(None)?);
+    }
+
+    let mut root = match &inputs[..] {
+        [dir] if dir.is_dir() => dir.clone(),
+        _ => inputs[0].parent().unwrap_or(&inputs[0]).to_path_buf(),
+    };
+    for input in &inputs[1..] {
+        while !input.starts_with(&root) {
+            root = root
+                .parent()
+                .context("paths have no common directory")?
+                .to_path_buf();
+        }
+        // A directory's parent, so that its name shows up in the tree.
+        if input == &root
+            && let Some(parent) = root.parent()
+        {
+            root = parent.to_path_buf();
+        }
+    }
+    Ok((root, inputs))
+}
+
+/// The root and inputs of a `--from-filelist` dump: the files of the list that exist under
+/// the root (PATH, or the working directory). The paths of the others go to `missing`.
+fn listed_inputs(
+    list: &Path,
+    args: &DumpArgs,
+    missing: &mut Vec<String>,
+) -> Result<(PathBuf, Vec<PathBuf>)> {
+    let mut paths = args.path.iter().chain(&args.paths);
+    let root_dir = resolve_root(paths.next().cloned())?;
+    if paths.next().is_some() || !root_dir.is_dir() {
+        bail!("--from-filelist takes a single directory as the root");
+    }
+    let mut inputs = Vec::new();
+    for relative_path in filelist::read(list)? {
+        let path = root_dir.join(&relative_path);
+        if path.exists() {
+            inputs.push(path);
+        } else {
+            missing.push(relative_path);
+        }
+    }
+    if inputs.is_empty() {
+        bail!("none of the files listed in {} exist", list.display());
+    }
+    Ok((root_dir, inputs))
+}
+
+fn collect_files(root: &Path, args: &CollectArgs, config: &Config) -> Result<Collection> {
+    collect_paths(root, &[root.to_path_buf()], args, config)
+}
+
+/// Collects the files under each of `inputs` (directories or single files), with paths
+/// relative to `root`.
+fn collect_paths(
+    root: &Path,
+    inputs: &[PathBuf],
+    args: &CollectArgs,
+    config: &Config,
+) -> Result<Collection> {
+    let filter = PathFilter::new(root, args, &presets::globs(root, &args.preset))?;
+    let recency = args
+        .since
+        .map(|since| Recency::new(root, since, args.since_source))
+        .transpose()?;
+    let limits = SizeLimits::new(root, args, config)?;
+    let preprocessors = Preprocessors::new(root, config)?;
+    let lines = match (args.head, args.tail) {
+        (Some(count), _) => Some(logs::Lines::Head(count)),
+        (None, Some(count)) => Some(logs::Lines::Tail(count)),
+        (None, None) => None,
+    };
+    let logs = LogFilter::new(root, &args.log_glob, lines)?;
+    let mut builder = WalkBuilder::new(&inputs[0]);
+    for input in &inputs[1..] {
+        builder.add(input);
+    }
     builder
-        .git_ignore(true)
-        .git_exclude(true)
-        .parents(true)
-        .hidden(false)
-        .follow_links(false)
-        .filter_entry(|entry| should_include(entry));
+        .git_ignore(!args.no_gitignore)
+        .git_global(!args.no_gitignore)
+        .git_exclude(!args.no_gitignore)
+        .parents(!args.no_ignore_parent)
+        .hidden(args.no_hidden)
+        .follow_links(args.follow_symlinks)
+        .filter_entry(should_include);
 
-    let mut files = Vec::new();
+    let mut entries = Vec::new();
     let mut skipped = Vec::new();
+    let mut symlinks = Vec::new();
+    let mut filtered = Vec::new();
 
+    progress::start(progress::Phase::Collecting);
     for entry in builder.build() {
         match entry {
             Ok(dir_entry) => {
-                if dir_entry.depth() == 0 {
-                    continue;
-                }
-
                 if dir_entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false) {
                     continue;
                 }
+                progress::scanned();
 
-                let metadata = match dir_entry.metadata() {
-                    Ok(meta) => meta,
-                    Err(err) => {
-                        skipped.push(SkippedFile {
-                            relative_path: to_relative(root, dir_entry.path()),
-                            reason: SkipReason::Io(err.to_string()),
-                        });
-                        continue;
-                    }
-                };
-
-                if !metadata.is_file() {
+                let relative_path = to_relative(root, dir_entry.path());
+                if !filter.matches(&relative_path) {
+                    filtered.push(relative_path);
                     continue;
                 }
 
-                if metadata.len() as usize > max_file_size {
-                    skipped.push(SkippedFile {
-                        relative_path: to_relative(root, dir_entry.path()),
-                        reason: SkipReason::TooLarge(metadata.len()),
+                if !args.follow_symlinks && dir_entry.path_is_symlink() {
+                    let target = fs::read_link(dir_entry.path())
+                        .map(|target| slashed(&target))
+                        .unwrap_or_else(|_| "?".to_string());
+                    symlinks.push(Symlink {
+                        relative_path,
+                        target,
                     });
                     continue;
                 }
 
-                let data = match fs::read(dir_entry.path()) {
-                    Ok(data) => data,
-                    Err(err) => {
-                        skipped.push(SkippedFile {
-                            relative_path: to_relative(root, dir_entry.path()),
-                            reason: SkipReason::Io(err.to_string()),
-                        });
-                        continue;
-                    }
-                };
+                if let Some(recency) = &recency
+                    && !recency.matches(&relative_path, &dir_entry)
+                {
+                    filtered.push(relative_path);
+                    continue;
+                }
 
-                let contents = match String::from_utf8(data) {
-                    Ok(text) => text,
-                    Err(_) => {
-                        skipped.push(SkippedFile {
-                            relative_path: to_relative(root, dir_entry.path()),
-                            reason: SkipReason::NonUtf8,
-                        });
-                        continue;
-                    }
+                entries.push(dir_entry);
+            }
+            Err(ref err) if let Some((ancestor, child)) = symlink_loop(err) => {
+                let ancestor = match to_relative(root, ancestor) {
+                    relative if relative.is_empty() => ".".to_string(),
+                    relative => relative,
                 };
-
-                files.push(FileDump {
-                    relative_path: to_relative(root, dir_entry.path()),
-                    contents,
+                skipped.push(SkippedFile {
+                    relative_path: to_relative(root, child),
+                    reason: SkipReason::SymlinkLoop(ancestor),
+                    bytes: None,
                 });
             }
             Err(err) => {
@@ -286,95 +2804,686 @@ fn collect_files(root: &Path, max_file_size: usize) -> Result<(Vec<FileDump>, Ve
                 skipped.push(SkippedFile {
                     relative_path: "<walker>".to_string(),
                     reason: SkipReason::Io(reason_message),
+                    bytes: None,
                 });
             }
         }
     }
 
+    // Reading and decoding dominate on large repos, so do it in parallel; `collect`
+    // keeps walk order and the final sort makes the result independent of it anyway.
+    let outcomes: Vec<Result<FileDump, SkippedFile>> = entries
+        .par_iter()
+        .filter_map(|dir_entry| {
+            read_entry(
+                root,
+                dir_entry,
+                args,
+                &limits,
+                &preprocessors,
+                &logs,
+                config,
+            )
+            .inspect(|outcome| match outcome {
+                Ok(file) => progress::included(file.contents.len()),
+                Err(_) => progress::skipped(),
+            })
+        })
+        .collect();
+    progress::finish();
+
+    let mut files = Vec::new();
+    for outcome in outcomes {
+        match outcome {
+            Ok(file) => files.push(file),
+            Err(skipped_file) => skipped.push(skipped_file),
+        }
+    }
+
+    Ok(finish_collection(files, skipped, symlinks, filtered, args))
+}
+
+/// Sorts and deduplicates what was collected, and applies `--native-separators`.
+fn finish_collection(
+    mut files: Vec<FileDump>,
+    mut skipped: Vec<SkippedFile>,
+    mut symlinks: Vec<Symlink>,
+    mut filtered: Vec<String>,
+    args: &CollectArgs,
+) -> Collection {
     files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
     skipped.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    symlinks.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    filtered.sort();
+
+    // Inputs may overlap (a directory and a file inside it), and archives may hold a path
+    // twice.
+    files.dedup_by(|a, b| a.relative_path == b.relative_path);
+    skipped.dedup_by(|a, b| a.relative_path == b.relative_path);
+    symlinks.dedup_by(|a, b| a.relative_path == b.relative_path);
+    filtered.dedup();
+
+    if args.native_separators && MAIN_SEPARATOR != '/' {
+        let native = |path: &mut String| *path = path.replace('/', MAIN_SEPARATOR_STR);
+        files
+            .iter_mut()
+            .for_each(|file| native(&mut file.relative_path));
+        skipped
+            .iter_mut()
+            .for_each(|file| native(&mut file.relative_path));
+        symlinks.iter_mut().for_each(|link| {
+            native(&mut link.relative_path);
+            native(&mut link.target);
+        });
+        filtered.iter_mut().for_each(native);
+    }
 
-    Ok((files, skipped))
+    Collection {
+        files,
+        skipped,
+        symlinks,
+        filtered,
+        sizes: HashMap::new(),
+    }
 }
 
-fn should_include(entry: &DirEntry) -> bool {
-    if entry.depth() == 0 {
-        return true;
+/// Collects the regular files of an archive as [`collect_paths`] does a directory's, reading
+/// each entry straight into memory. Entries over the size limit are skipped by the size the
+/// archive declares, before anything is decompressed, so the limit applies to notebooks as
+/// stored; logs are excerpted as they are read. `.gitignore` files in the archive apply as in
+/// a checkout.
+fn collect_archive(path: &Path, args: &CollectArgs, config: &Config) -> Result<Collection> {
+    if args.since.is_some() && args.since_source == recency::Source::Git {
+        bail!("--since-source git does not apply to --archive");
     }
+    // Globs match paths inside the archive.
+    let root = Path::new("");
+    let filter = PathFilter::new(root, args, &[])?;
+    let limits = SizeLimits::new(root, args, config)?;
+    let preprocessors = Preprocessors::new(root, config)?;
+    let lines = match (args.head, args.tail) {
+        (Some(count), _) => Some(logs::Lines::Head(count)),
+        (None, Some(count)) => Some(logs::Lines::Tail(count)),
+        (None, None) => None,
+    };
+    let logs = LogFilter::new(root, &args.log_glob, lines)?;
+    // Preprocessors run next to the archive.
+    let dir = path.parent().unwrap_or(Path::new("."));
+
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+    let mut filtered = Vec::new();
+    let mut paths = Vec::new();
+    let mut sizes = HashMap::new();
+    let mut gitignores = Vec::new();
+
+    progress::start(progress::Phase::Collecting);
+    archive::read(path, |entry| {
+        progress::scanned();
+        let relative_path = entry.name;
+        let (dirs, name) = relative_path
+            .rsplit_once('/')
+            .unwrap_or(("", &relative_path));
+        let hidden_dir = dirs.split('/').any(|dir| dir.starts_with('.'));
+        if dirs
+            .split('/')
+            .any(|dir| DEFAULT_IGNORED_DIRS.contains(&dir))
+            || (args.no_hidden && hidden_dir)
+        {
+            return Ok(());
+        }
+        let is_gitignore = !args.no_gitignore && name == ".gitignore";
+        let gitignore_dir = dirs.to_string();
+        paths.push(relative_path.clone());
+        sizes.insert(relative_path.clone(), entry.size);
+        // Hidden `.gitignore` files still apply, as they do in a walk.
+        let listed = !(args.no_hidden && name.starts_with('.'));
+        if !listed && !is_gitignore {
+            return Ok(());
+        }
+
+        let included = filter.matches(&relative_path)
+            && args
+                .since
+                .is_none_or(|since| entry.modified.is_some_and(|modified| modified >= since));
+        let max_size = limits.for_path(&relative_path);
+        // Logs are excerpted first, as for files on disk, and checked against the limit after.
+        let log_lines = logs.lines_for(&relative_path);
+        let too_large = log_lines.is_none() && entry.size > max_size as u64;
+        let keep = listed && included && (!too_large || args.truncate_large_files);
+        let read_rules = is_gitignore && entry.size <= max_size as u64;
+        if listed && !included {
+            filtered.push(relative_path.clone());
+        } else if listed && !keep {
+            skipped.push(SkippedFile {
+                relative_path: relative_path.clone(),
+                reason: SkipReason::TooLarge(entry.size),
+                bytes: Some(entry.size),
+            });
+        }
+        // `.gitignore` files are read for their rules even when they are left out.
+        if !keep && !read_rules {
+            return Ok(());
+        }
+
+        // A few bytes past the limit, so that truncated contents end in whole characters.
+        let mut data = Vec::new();
+        let read = match log_lines.filter(|_| keep) {
+            Some(lines) => logs::excerpt_from(&mut *entry.data, lines, max_size as u64 + 4)
+                .map(|excerpt| data = excerpt),
+            None => Read::take(&mut *entry.data, max_size as u64 + 4)
+                .read_to_end(&mut data)
+                .map(drop),
+        };
+        let skip = |reason| SkippedFile {
+            relative_path: relative_path.clone(),
+            reason,
+            bytes: Some(entry.size),
+        };
+        if let Err(err) = read {
+            if keep {
+                skipped.push(skip(SkipReason::Io(err.to_string())));
+            }
+            return Ok(());
+        }
+        if read_rules {
+            gitignores.push((gitignore_dir, data.clone()));
+        }
+        if !keep {
+            return Ok(());
+        }
+
+        let mut size = entry.size;
+        let mut unread_lines = 0;
+        if too_large {
+            unread_lines = match unread_lines_after(&data, entry.data) {
+                Ok(lines) => lines,
+                Err(err) => {
+                    skipped.push(skip(SkipReason::Io(err.to_string())));
+                    return Ok(());
+                }
+            };
+            if let Err(err) = std::str::from_utf8(&data)
+                && err.error_len().is_none()
+            {
+                data.truncate(err.valid_up_to());
+            }
+        }
+        let mut excerpt_of = None;
+        if let Some(lines) = log_lines
+            && (data.len() as u64) < entry.size
+        {
+            excerpt_of = Some((lines, entry.size - data.len() as u64));
+            size = data.len() as u64;
+        }
+        if let Some(command) = preprocessors.command_for(&relative_path) {
+            match preprocess::run(command, dir, Path::new(&relative_path), &data) {
+                Ok(output) => data = output,
+                Err(err) => {
+                    skipped.push(skip(SkipReason::Preprocessor(err)));
+                    return Ok(());
+                }
+            }
+            size = data.len() as u64;
+            unread_lines = 0;
+        }
+
+        let read = ReadFile {
+            relative_path: relative_path.clone(),
+            data,
+            size,
+            stored_size: entry.size,
+            excerpt_of,
+            unread_lines,
+            max_size,
+            modified: entry.modified,
+            source: None,
+        };
+        match decode_file(read, args, config) {
+            Ok(file) => {
+                progress::included(file.contents.len());
+                files.push(file);
+            }
+            Err(reason) => {
+                progress::skipped();
+                skipped.push(skip(reason));
+            }
+        }
+        Ok(())
+    })?;
+    progress::finish();
 
-    if entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false) {
-        if let Some(name) = entry.file_name().to_str() {
-            return !DEFAULT_IGNORED_DIRS.contains(&name);
+    // `.gitignore` files may come after the entries they apply to, and presets depend on
+    // which marker files there are, so both apply once everything was seen.
+    let ignores = archive_ignores(gitignores)?;
+    let presets = presets::globs_for_paths(&paths, &args.preset);
+    let preset_filter = PathFilter::new(root, args, &presets)?;
+    let ignored = |path: &str| {
+        for (dir, gitignore) in &ignores {
+            if !dir.is_empty() && !path.starts_with(&format!("{dir}/")) {
+                continue;
+            }
+            match gitignore.matched_path_or_any_parents(path, false) {
+                ignore::Match::Ignore(_) => return true,
+                ignore::Match::Whitelist(_) => return false,
+                ignore::Match::None => {}
+            }
         }
+        false
+    };
+    files.retain(|file| !ignored(&file.relative_path));
+    skipped.retain(|file| !ignored(&file.relative_path));
+    filtered.retain(|path| !ignored(path));
+    let (files, dropped): (Vec<_>, Vec<_>) = files
+        .into_iter()
+        .partition(|file| preset_filter.matches(&file.relative_path));
+    filtered.extend(dropped.into_iter().map(|file| file.relative_path));
+    let (skipped, dropped): (Vec<_>, Vec<_>) = skipped
+        .into_iter()
+        .partition(|file| preset_filter.matches(&file.relative_path));
+    filtered.extend(dropped.into_iter().map(|file| file.relative_path));
+
+    if args.native_separators && MAIN_SEPARATOR != '/' {
+        sizes = sizes
+            .into_iter()
+            .map(|(path, size)| (path.replace('/', MAIN_SEPARATOR_STR), size))
+            .collect();
     }
+    Ok(Collection {
+        sizes,
+        ..finish_collection(files, skipped, Vec::new(), filtered, args)
+    })
+}
 
-    true
+/// Lines that the rest of `data` adds to contents read up to `head`, read through without
+/// keeping it.
+fn unread_lines_after(head: &[u8], data: &mut dyn Read) -> io::Result<usize> {
+    let open_before = head.last().is_some_and(|&byte| byte != b'\n');
+    let mut open = open_before;
+    let mut lines = 0;
+    let mut buf = [0; 64 * 1024];
+    loop {
+        let read = match data.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        lines += buf[..read].iter().filter(|&&byte| byte == b'\n').count();
+        open = buf[read - 1] != b'\n';
+    }
+    Ok(lines + open as usize - open_before as usize)
 }
 
-fn to_relative(root: &Path, path: &Path) -> String {
-    path.strip_prefix(root)
-        .map(|p| p.display().to_string())
-        .unwrap_or_else(|_| path.display().to_string())
+/// Matchers for the `.gitignore` files of an archive, given as (directory, contents), deepest
+/// first so that the closest file with a matching rule decides.
+fn archive_ignores(gitignores: Vec<(String, Vec<u8>)>) -> Result<Vec<(String, Gitignore)>> {
+    let mut ignores = Vec::new();
+    for (dir, contents) in gitignores {
+        let mut builder = GitignoreBuilder::new(&dir);
+        for line in String::from_utf8_lossy(&contents).lines() {
+            // Invalid lines are skipped, as Git does.
+            let _ = builder.add_line(None, line);
+        }
+        ignores.push((dir, builder.build()?));
+    }
+    ignores.sort_by_key(|(dir, _)| {
+        std::cmp::Reverse(dir.matches('/').count() + !dir.is_empty() as usize)
+    });
+    Ok(ignores)
 }
 
-#[derive(Default)]
-struct TreeNode {
-    children: BTreeMap<String, TreeNode>,
-    is_file: bool,
+/// Default for `--max-file-size`.
+const MAX_FILE_SIZE: usize = 64_000;
+
+/// Size limits from `--max-file-size` and the config, resolved per file.
+struct SizeLimits {
+    default: usize,
+    /// In increasing precedence.
+    patterns: Vec<(Gitignore, usize)>,
 }
 
-impl TreeNode {
-    fn insert(&mut self, components: &[&str]) {
-        if let Some((first, rest)) = components.split_first() {
-            let child = self.children.entry((*first).to_string()).or_default();
-            if rest.is_empty() {
-                child.is_file = true;
-            } else {
-                child.insert(rest);
+impl SizeLimits {
+    fn new(root: &Path, args: &CollectArgs, config: &Config) -> Result<Self> {
+        let mut limits = Vec::new();
+        for (glob, size) in &config.max_file_sizes {
+            let bytes = size
+                .bytes()
+                .map_err(|err| anyhow!("invalid size for {glob} in [max_file_sizes]: {err}"))?;
+            limits.push((Some(glob.as_str()), bytes));
+        }
+        limits.extend(
+            args.max_file_size
+                .iter()
+                .map(|limit| (limit.glob.as_deref(), limit.bytes)),
+        );
+
+        let mut default = MAX_FILE_SIZE;
+        let mut patterns = Vec::new();
+        for (glob, bytes) in limits {
+            let Some(glob) = glob else {
+                default = bytes;
+                continue;
+            };
+            let mut builder = GitignoreBuilder::new(root);
+            builder
+                .add_line(None, glob)
+                .with_context(|| format!("invalid glob {glob}"))?;
+            patterns.push((builder.build()?, bytes));
+        }
+        Ok(SizeLimits { default, patterns })
+    }
+
+    fn for_path(&self, relative_path: &str) -> usize {
+        self.patterns
+            .iter()
+            .rev()
+            .find(|(glob, _)| {
+                glob.matched_path_or_any_parents(relative_path, false)
+                    .is_ignore()
+            })
+            .map_or(self.default, |&(_, bytes)| bytes)
+    }
+}
+
+/// `--include` / `--exclude` globs, matched against paths relative to the root.
+///
+/// Applied on top of `.gitignore` rather than overriding it, unlike walker overrides.
+struct PathFilter {
+    include: Option<Gitignore>,
+    exclude: Gitignore,
+}
+
+impl PathFilter {
+    /// `presets` are the `--preset` globs, added to `--exclude`.
+    fn new(root: &Path, args: &CollectArgs, presets: &[String]) -> Result<Self> {
+        let build = |globs: &[String]| -> Result<Gitignore> {
+            let mut builder = GitignoreBuilder::new(root);
+            for glob in globs {
+                builder
+                    .add_line(None, glob)
+                    .with_context(|| format!("invalid glob {glob}"))?;
             }
+            Ok(builder.build()?)
+        };
+        Ok(PathFilter {
+            include: (!args.include.is_empty())
+                .then(|| build(&args.include))
+                .transpose()?,
+            exclude: build(&[&args.exclude[..], presets].concat())?,
+        })
+    }
+
+    fn matches(&self, relative_path: &str) -> bool {
+        let hit = |globs: &Gitignore| {
+            globs
+                .matched_path_or_any_parents(relative_path, false)
+                .is_ignore()
+        };
+        self.include.as_ref().is_none_or(hit) && !hit(&self.exclude)
+    }
+}
+
+/// Returns the `(ancestor, child)` paths if the walker stopped at a symlink loop.
+fn symlink_loop(err: &ignore::Error) -> Option<(&Path, &Path)> {
+    match err {
+        ignore::Error::Loop { ancestor, child } => Some((ancestor, child)),
+        ignore::Error::WithPath { err, .. } | ignore::Error::WithDepth { err, .. } => {
+            symlink_loop(err)
         }
+        _ => None,
     }
 }
 
-fn build_file_tree(files: &[FileDump]) -> String {
-    let mut root = TreeNode::default();
-    for file in files {
-        let path = Path::new(&file.relative_path);
-        let components: Vec<String> = path
-            .components()
-            .map(|component| component.as_os_str().to_string_lossy().into_owned())
-            .collect();
-        let parts: Vec<&str> = components.iter().map(String::as_str).collect();
-        if parts.is_empty() {
-            continue;
+/// Reads one walked entry. Returns `None` for entries that are not regular files.
+fn read_entry(
+    root: &Path,
+    dir_entry: &DirEntry,
+    args: &CollectArgs,
+    limits: &SizeLimits,
+    preprocessors: &Preprocessors,
+    logs: &LogFilter,
+    config: &Config,
+) -> Option<Result<FileDump, SkippedFile>> {
+    let metadata = match dir_entry.metadata() {
+        Ok(meta) => meta,
+        Err(err) => {
+            return Some(Err(SkippedFile {
+                relative_path: to_relative(root, dir_entry.path()),
+                reason: SkipReason::Io(err.to_string()),
+                bytes: None,
+            }));
         }
-        root.insert(&parts);
+    };
+    let skip = |reason| {
+        Some(Err(SkippedFile {
+            relative_path: to_relative(root, dir_entry.path()),
+            reason,
+            bytes: Some(metadata.len()),
+        }))
+    };
+
+    if !metadata.is_file() {
+        return None;
+    }
+
+    // Notebooks are mostly outputs and metadata, so their limit applies after flattening.
+    let relative_path = to_relative(root, dir_entry.path());
+    let is_notebook = relative_path.ends_with(".ipynb");
+    let max_size = limits.for_path(&relative_path);
+    // Log excerpts are taken before the limit applies, so large logs can still be included.
+    let lines = logs.lines_for(&relative_path);
+    let too_large = metadata.len() as usize > max_size;
+    if too_large && !args.truncate_large_files && !is_notebook && lines.is_none() {
+        return skip(SkipReason::TooLarge(metadata.len()));
+    }
+
+    // Only contents read as they are can be looked up in the token cache, by the file's
+    // metadata.
+    let preprocessor = preprocessors.command_for(&relative_path);
+    let source = (lines.is_none() && !is_notebook && preprocessor.is_none())
+        .then(|| cache::Source::new(dir_entry.path(), &metadata))
+        .flatten();
+    let unread =
+        args.counts_only && !too_large && source.as_ref().and_then(cache::cached).is_some();
+
+    let mut size = metadata.len();
+    let mut excerpt_of = None;
+    let read = match lines {
+        _ if unread => Ok(Vec::new()),
+        Some(lines) => logs::excerpt(dir_entry.path(), lines).map(|(data, cut)| {
+            if cut {
+                excerpt_of = Some((lines, size - data.len() as u64));
+                size = data.len() as u64;
+            }
+            data
+        }),
+        None => fs::read(dir_entry.path()),
+    };
+    let mut data = match read {
+        Ok(data) => data,
+        Err(err) => return skip(SkipReason::Io(err.to_string())),
+    };
+    if let Some(command) = preprocessor {
+        data = match preprocess::run(command, root, dir_entry.path(), &data) {
+            Ok(output) => output,
+            Err(err) => return skip(SkipReason::Preprocessor(err)),
+        };
+        size = data.len() as u64;
     }
 
-    let mut lines = Vec::new();
-    lines.push(".".to_string());
-    render_tree(&root, "", &mut lines);
-    lines.join("\n")
+    let read = ReadFile {
+        relative_path,
+        data,
+        size,
+        stored_size: metadata.len(),
+        excerpt_of,
+        unread_lines: 0,
+        max_size,
+        modified: metadata.modified().ok(),
+        source,
+    };
+    match decode_file(read, args, config) {
+        Ok(file) => Some(Ok(file)),
+        Err(reason) => skip(reason),
+    }
+}
+
+/// A file's bytes as read from disk or an archive, for [`decode_file`].
+struct ReadFile {
+    relative_path: String,
+    data: Vec<u8>,
+    /// Size of the contents `data` stands for, checked against `max_size`: as stored, or of
+    /// the excerpt or preprocessor output.
+    size: u64,
+    /// Size on disk or in the archive.
+    stored_size: u64,
+    /// Lines kept and bytes left out, if `data` is a log excerpt.
+    excerpt_of: Option<(logs::Lines, u64)>,
+    /// Lines past the end of `data` that were never read, for the truncation marker.
+    unread_lines: usize,
+    max_size: usize,
+    modified: Option<SystemTime>,
+    source: Option<cache::Source>,
 }
 
-fn render_tree(node: &TreeNode, prefix: &str, lines: &mut Vec<String>) {
-    let total = node.children.len();
-    for (idx, (name, child)) in node.children.iter().enumerate() {
-        let is_last = idx + 1 == total;
-        let connector = if is_last { "`-- " } else { "|-- " };
-        let mut line = String::new();
-        line.push_str(prefix);
-        line.push_str(connector);
-        line.push_str(name);
-        if !child.children.is_empty() && !child.is_file {
-            line.push('/');
+/// Decodes a file, marks log excerpts, flattens notebooks and truncates (or skips) contents
+/// over the size limit.
+fn decode_file(
+    read: ReadFile,
+    args: &CollectArgs,
+    config: &Config,
+) -> Result<FileDump, SkipReason> {
+    let ReadFile {
+        relative_path,
+        data,
+        mut size,
+        stored_size,
+        excerpt_of,
+        unread_lines,
+        max_size,
+        modified,
+        mut source,
+    } = read;
+    let (mut contents, encoding) =
+        decode::decode(data, args.lossy_utf8).ok_or(SkipReason::NonUtf8)?;
+    if encoding.is_some() {
+        source = None;
+    }
+
+    match excerpt_of {
+        Some((logs::Lines::Head(_), omitted)) => {
+            if !contents.is_empty() && !contents.ends_with('\n') {
+                contents.push('\n');
+            }
+            contents.push_str(&format!(
+                "[... {} more bytes ...]\n",
+                with_thousands(omitted as usize)
+            ));
         }
-        lines.push(line);
+        Some((logs::Lines::Tail(_), omitted)) => contents.insert_str(
+            0,
+            &format!(
+                "[... {} earlier bytes ...]\n",
+                with_thousands(omitted as usize)
+            ),
+        ),
+        None => {}
+    }
+
+    let mut language = None;
+    if relative_path.ends_with(".ipynb")
+        && let Some((script, kernel_language)) = notebook::flatten(&contents)
+    {
+        contents = script;
+        size = contents.len() as u64;
+        language = kernel_language;
+    }
+    let too_large = size as usize > max_size;
+    if too_large && !args.truncate_large_files {
+        return Err(SkipReason::TooLarge(size));
+    }
+
+    let truncated_from = too_large
+        .then(|| {
+            truncate_contents(&mut contents, max_size, unread_lines);
+            source = None;
+            size
+        })
+        .or(excerpt_of.map(|_| stored_size));
+
+    let language = language.or_else(|| lang::detect(&relative_path, &contents, &config.languages));
+    Ok(FileDump {
+        relative_path,
+        contents,
+        language,
+        truncated_from,
+        encoding,
+        tokens: None,
+        modified,
+        info: None,
+        summarized_from: None,
+        duplicate_of: None,
+        change: None,
+        condensed: None,
+        source,
+    })
+}
+
+/// Keeps at most `max_bytes` of `contents`, cut after the last complete line if there is
+/// one, and appends a marker saying how many lines were dropped.
+fn truncate_contents(contents: &mut String, max_bytes: usize, unread_lines: usize) {
+    let mut end = max_bytes.min(contents.len());
+    while !contents.is_char_boundary(end) {
+        end -= 1;
+    }
+    if let Some(newline) = contents[..end].rfind('\n') {
+        end = newline + 1;
+    }
+
+    let dropped_lines = contents[end..].lines().count() + unread_lines;
+    contents.truncate(end);
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(&format!(
+        "[... truncated, {} more lines ...]\n",
+        with_thousands(dropped_lines)
+    ));
+}
 
-        if !child.children.is_empty() {
-            let mut new_prefix = String::from(prefix);
-            new_prefix.push_str(if is_last { "    " } else { "|   " });
-            render_tree(child, &new_prefix, lines);
+/// Formats `n` with `,` as the thousands separator, e.g. `12,400`.
+fn with_thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (idx, digit) in digits.chars().enumerate() {
+        if idx > 0 && (digits.len() - idx).is_multiple_of(3) {
+            out.push(',');
         }
+        out.push(digit);
+    }
+    out
+}
+
+fn should_include(entry: &DirEntry) -> bool {
+    if entry.depth() == 0 {
+        return true;
+    }
+
+    if entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false)
+        && let Some(name) = entry.file_name().to_str()
+    {
+        return !DEFAULT_IGNORED_DIRS.contains(&name);
     }
+
+    true
+}
+
+/// `path` relative to `root`, with `/` separators on every platform.
+fn to_relative(root: &Path, path: &Path) -> String {
+    slashed(path.strip_prefix(root).unwrap_or(path))
+}
+
+fn slashed(path: &Path) -> String {
+    path.display().to_string().replace(MAIN_SEPARATOR, "/")
 }
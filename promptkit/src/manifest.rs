@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use serde::Serialize;
+use tiktoken_rs::CoreBPE;
+
+use crate::{FileDump, SkippedFile, TOKENIZER_NAME};
+
+/// JSON record of what went into a dump, for auditing what was sent to a model.
+#[derive(Serialize)]
+pub struct Manifest<'a> {
+    pub root: &'a str,
+    pub tokenizer: &'static str,
+    /// Tokens of the whole prompt (summed over parts when chunked).
+    pub tokens: usize,
+    pub included: Vec<IncludedEntry<'a>>,
+    pub skipped: Vec<SkippedEntry<'a>>,
+}
+
+#[derive(Serialize)]
+pub struct IncludedEntry<'a> {
+    pub path: &'a str,
+    pub bytes: usize,
+    pub tokens: usize,
+    /// Size on disk when only the head of the file was included.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub truncated_from: Option<u64>,
+    /// Tokens of the original contents when a summary was included instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summarized_from: Option<usize>,
+    /// Path of the file with the same contents, which were left out of this one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<&'a str>,
+    /// `lockfile`, `minified` or `vendored` when a summary was included instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub condensed: Option<&'static str>,
+    /// `added`, `removed` or `modified`, with `--compare`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change: Option<&'static str>,
+    /// Encoding the file was transcoded from, if it was not plain UTF-8.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+pub struct SkippedEntry<'a> {
+    pub path: &'a str,
+    pub reason: &'static str,
+    pub detail: String,
+}
+
+impl<'a> Manifest<'a> {
+    pub fn new(
+        root: &'a str,
+        tokens: usize,
+        files: &'a [FileDump],
+        skipped: &'a [SkippedFile],
+        tokenizer: &CoreBPE,
+    ) -> Self {
+        let included = files
+            .par_iter()
+            .map(|file| IncludedEntry {
+                path: &file.relative_path,
+                bytes: file.contents.len(),
+                tokens: crate::cache::count(tokenizer, file),
+                truncated_from: file.truncated_from,
+                summarized_from: file.summarized_from,
+                duplicate_of: file.duplicate_of.as_deref(),
+                condensed: file.condensed.map(|(kind, _)| kind.label()),
+                change: file.change.map(|change| change.label()),
+                encoding: file.encoding.map(|encoding| encoding.name()),
+            })
+            .collect();
+        let skipped = skipped
+            .iter()
+            .map(|file| SkippedEntry {
+                path: &file.relative_path,
+                reason: file.reason.kind(),
+                detail: file.reason.to_string(),
+            })
+            .collect();
+
+        Manifest {
+            root,
+            tokenizer: TOKENIZER_NAME,
+            tokens,
+            included,
+            skipped,
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json + "\n").with_context(|| format!("failed to write {}", path.display()))
+    }
+}
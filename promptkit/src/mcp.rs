@@ -0,0 +1,309 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use clap::{Args, FromArgMatches};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tiktoken_rs::CoreBPE;
+
+use crate::config::Config;
+use crate::{DumpArgs, StatsArgs};
+
+const SUPPORTED_PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+#[derive(Deserialize)]
+struct Request {
+    /// Absent for notifications, which get no response.
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct ToolCall {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Deserialize)]
+struct DumpContextInput {
+    task: String,
+    path: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct GetFileInput {
+    path: String,
+}
+
+#[derive(Deserialize)]
+struct RepoStatsInput {
+    path: Option<String>,
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+/// Minimal Model Context Protocol server over stdio (newline-delimited JSON-RPC 2.0).
+pub struct Server {
+    root: PathBuf,
+    config: Config,
+    tokenizer: CoreBPE,
+}
+
+impl Server {
+    pub fn new(root: PathBuf, config: Config, tokenizer: CoreBPE) -> Self {
+        Self {
+            root,
+            config,
+            tokenizer,
+        }
+    }
+
+    /// Serves requests from stdin until it is closed.
+    pub fn run(&self) -> Result<()> {
+        eprintln!("promptkit MCP server: root={}", self.root.display());
+        let stdin = io::stdin().lock();
+        let mut stdout = io::stdout().lock();
+
+        for line in stdin.lines() {
+            let line = line.context("failed to read from stdin")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(request) => match request.id {
+                    Some(id) => Some(match self.handle(&request.method, request.params) {
+                        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                        Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+                    }),
+                    None => None,
+                },
+                Err(err) => Some(json!({
+                    "jsonrpc": "2.0",
+                    "id": null,
+                    "error": { "code": -32700, "message": format!("parse error: {err}") },
+                })),
+            };
+            if let Some(response) = response {
+                writeln!(stdout, "{}", response)?;
+                stdout.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the JSON-RPC result, or a JSON-RPC error object.
+    fn handle(&self, method: &str, params: Value) -> Result<Value, Value> {
+        match method {
+            "initialize" => {
+                let requested = params.get("protocolVersion").and_then(Value::as_str);
+                let version = requested
+                    .filter(|version| SUPPORTED_PROTOCOL_VERSIONS.contains(version))
+                    .unwrap_or(SUPPORTED_PROTOCOL_VERSIONS[0]);
+                Ok(json!({
+                    "protocolVersion": version,
+                    "capabilities": { "tools": {} },
+                    "serverInfo": { "name": "promptkit", "version": env!("CARGO_PKG_VERSION") },
+                }))
+            }
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tool_definitions() })),
+            "tools/call" => {
+                let call: ToolCall = serde_json::from_value(params)
+                    .map_err(|err| json!({ "code": -32602, "message": err.to_string() }))?;
+                // Tool failures are reported in the result so the model can see them.
+                Ok(match self.call_tool(&call) {
+                    Ok(text) => json!({ "content": [{ "type": "text", "text": text }] }),
+                    Err(err) => json!({
+                        "content": [{ "type": "text", "text": format!("{err:#}") }],
+                        "isError": true,
+                    }),
+                })
+            }
+            _ => Err(json!({ "code": -32601, "message": format!("method not found: {method}") })),
+        }
+    }
+
+    fn call_tool(&self, call: &ToolCall) -> Result<String> {
+        match call.name.as_str() {
+            "dump_context" => {
+                let input: DumpContextInput = serde_json::from_value(call.arguments.clone())?;
+                let mut argv = vec![input.task];
+                argv.extend(input.args);
+                let mut args: DumpArgs = parse_args(argv.clone())?;
+                if let Some(name) = &args.profile {
+                    let profile = self.config.profile(name)?;
+                    args = parse_args(profile.iter().cloned().chain(argv).collect())?;
+                }
+                // Paths from the input, the arguments and the profile are all relative to the
+                // server root and may not leave it.
+                let paths: Vec<PathBuf> = input
+                    .path
+                    .iter()
+                    .map(PathBuf::from)
+                    .chain(args.path.drain(..))
+                    .chain(args.paths.drain(..))
+                    .collect();
+                for path in &paths {
+                    args.path.push(self.resolve(&path.to_string_lossy())?);
+                }
+                if let Some(dirs) = args.compare.take() {
+                    let dirs = dirs
+                        .iter()
+                        .map(|dir| self.resolve(&dir.to_string_lossy()))
+                        .collect::<Result<_>>()?;
+                    args.compare = Some(dirs);
+                } else if let Some(archive) = args.archive.take() {
+                    args.archive = Some(self.resolve(&archive.to_string_lossy())?);
+                } else if args.path.is_empty() {
+                    args.path.push(self.root.clone());
+                }
+                if args.output.is_some()
+                    || args.chunk_dir.is_some()
+                    || args.manifest.is_some()
+                    || args.anonymize.is_some()
+                    || args.write_filelist.is_some()
+                    || args.export.is_some()
+                    || args.repo.is_some()
+                    || args.collect.follow_symlinks
+                    || args.summarize_over.is_some()
+                {
+                    bail!(
+                        "--output, --chunk-dir, --manifest, --anonymize, --write-filelist, --export, --repo, --follow-symlinks and --summarize-over are not available over MCP"
+                    );
+                }
+                if let Some(list) = args.from_filelist.take() {
+                    args.from_filelist = Some(self.resolve(&list.to_string_lossy())?);
+                }
+                // Redaction protects the user, so it is not the model's to turn off.
+                if args.no_redact {
+                    bail!("--no-redact is not available over MCP");
+                }
+                if args.task_file.iter().any(|path| path.as_os_str() == "-") {
+                    bail!("--task-file - is not available over MCP; stdin carries the protocol");
+                }
+                args.task_file = args
+                    .task_file
+                    .iter()
+                    .map(|path| self.resolve(&path.to_string_lossy()))
+                    .collect::<Result<_>>()?;
+
+                let task = args.read_task()?;
+                let dump = crate::build_dump(&args, &task, &self.config, &self.tokenizer)?;
+                let mut text = Vec::new();
+                crate::write_joined(dump.parts.len(), &mut text, |idx, out| {
+                    dump.stream_part(idx, out, None)?;
+                    Ok(())
+                })?;
+                let text = String::from_utf8(text)?;
+                Ok(text)
+            }
+            "get_file" => {
+                let input: GetFileInput = serde_json::from_value(call.arguments.clone())?;
+                let path = self.resolve(&input.path)?;
+                let contents = fs::read_to_string(&path)
+                    .with_context(|| format!("failed to read {}", input.path))?;
+                Ok(match crate::redact::redact(&input.path, &contents) {
+                    Some((redacted, _)) => redacted,
+                    None => contents,
+                })
+            }
+            "repo_stats" => {
+                let input: RepoStatsInput = serde_json::from_value(call.arguments.clone())?;
+                let path = self.resolve(input.path.as_deref().unwrap_or("."))?;
+                let mut argv = vec![path.display().to_string()];
+                argv.extend(input.args);
+                let mut args: StatsArgs = parse_args(argv)?;
+                // Links could reach outside the root.
+                if args.collect.follow_symlinks {
+                    bail!("--follow-symlinks is not available over MCP");
+                }
+                args.collect.counts_only = true;
+
+                let collection = crate::collect_files(&path, &args.collect, &self.config)?;
+                let breakdown =
+                    crate::stats::Breakdown::new(&collection.files, &self.tokenizer, args.depth);
+                Ok(breakdown.render(args.sort, args.top))
+            }
+            name => bail!("unknown tool: {name}"),
+        }
+    }
+
+    /// Resolves a path relative to the server root, refusing to escape it.
+    fn resolve(&self, relative: &str) -> Result<PathBuf> {
+        let path = self
+            .root
+            .join(relative)
+            .canonicalize()
+            .with_context(|| format!("failed to resolve path {relative}"))?;
+        if !path.starts_with(&self.root) {
+            bail!("path {relative} is outside of {}", self.root.display());
+        }
+        Ok(path)
+    }
+}
+
+/// Parses tool arguments with the same clap definitions as the CLI subcommands.
+fn parse_args<T: Args + FromArgMatches>(argv: Vec<String>) -> Result<T> {
+    let command = T::augment_args(
+        clap::Command::new("promptkit")
+            .no_binary_name(true)
+            .args_override_self(true),
+    );
+    let matches = command.try_get_matches_from(argv)?;
+    Ok(T::from_arg_matches(&matches)?)
+}
+
+fn tool_definitions() -> Value {
+    let args_schema = |description: &str| {
+        json!({
+            "type": "array",
+            "items": { "type": "string" },
+            "description": description,
+        })
+    };
+    json!([
+        {
+            "name": "dump_context",
+            "description": "Render repository context (file tree and file contents) followed by a task, as a prompt.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "task": { "type": "string", "description": "What should be done with the context." },
+                    "path": { "type": "string", "description": "Directory to dump, relative to the server root." },
+                    "args": args_schema("Extra `promptkit dump` flags, e.g. [\"--order\", \"relevance\", \"--max-tokens\", \"50000\"]."),
+                },
+                "required": ["task"],
+            },
+        },
+        {
+            "name": "get_file",
+            "description": "Read one file, with detected secrets redacted.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "File path relative to the server root." },
+                },
+                "required": ["path"],
+            },
+        },
+        {
+            "name": "repo_stats",
+            "description": "Per-directory and per-extension breakdown of files, bytes and tokens.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Directory to analyze, relative to the server root." },
+                    "args": args_schema("Extra `promptkit stats` flags, e.g. [\"--top\", \"10\", \"--depth\", \"2\"]."),
+                },
+            },
+        },
+    ])
+}
@@ -0,0 +1,91 @@
+use serde_json::Value;
+
+/// Text outputs longer than this are cut, with a note of how many lines were dropped.
+const MAX_OUTPUT_LINES: usize = 20;
+
+/// Flattens a Jupyter notebook into a script in the "percent" format (`# %%` cell markers).
+///
+/// Markdown cells become comments, text outputs are kept as comments (truncated), and rich
+/// outputs (images, HTML) and all metadata are dropped. Returns the script and the kernel
+/// language, or `None` if `json` is not a notebook.
+pub fn flatten(json: &str) -> Option<(String, Option<String>)> {
+    let notebook: Value = serde_json::from_str(json).ok()?;
+    let cells = notebook.get("cells")?.as_array()?;
+    let language = notebook
+        .pointer("/metadata/kernelspec/language")
+        .or_else(|| notebook.pointer("/metadata/language_info/name"))
+        .and_then(Value::as_str)
+        .map(str::to_lowercase);
+
+    let mut script = String::new();
+    for (idx, cell) in cells.iter().enumerate() {
+        if idx > 0 {
+            script.push('\n');
+        }
+        let source = text(cell.get("source"));
+        match cell.get("cell_type").and_then(Value::as_str) {
+            Some("code") => {
+                script.push_str("# %%\n");
+                push_lines(&mut script, &source, "");
+                let outputs = cell.get("outputs").and_then(Value::as_array);
+                for output in outputs.into_iter().flatten() {
+                    push_output(&mut script, output);
+                }
+            }
+            Some("markdown") => {
+                script.push_str("# %% [markdown]\n");
+                push_lines(&mut script, &source, "# ");
+            }
+            _ => {
+                script.push_str("# %% [raw]\n");
+                push_lines(&mut script, &source, "# ");
+            }
+        }
+    }
+    Some((script, language))
+}
+
+/// Notebook text fields are either a string or a list of line strings.
+fn text(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(lines)) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+fn push_lines(script: &mut String, text: &str, prefix: &str) {
+    for line in text.lines() {
+        script.push_str(prefix);
+        script.push_str(line);
+        script.push('\n');
+    }
+}
+
+fn push_output(script: &mut String, output: &Value) {
+    let text = match output.get("output_type").and_then(Value::as_str) {
+        Some("stream") => text(output.get("text")),
+        Some("execute_result" | "display_data") => text(output.pointer("/data/text~1plain")),
+        Some("error") => {
+            let name = output.get("ename").and_then(Value::as_str).unwrap_or("");
+            let value = output.get("evalue").and_then(Value::as_str).unwrap_or("");
+            format!("{name}: {value}")
+        }
+        _ => String::new(),
+    };
+    if text.trim().is_empty() {
+        return;
+    }
+
+    script.push_str("# Output:\n");
+    let lines: Vec<&str> = text.lines().collect();
+    for line in lines.iter().take(MAX_OUTPUT_LINES) {
+        script.push_str(&format!("# {line}\n"));
+    }
+    if lines.len() > MAX_OUTPUT_LINES {
+        script.push_str(&format!(
+            "# ... {} more lines\n",
+            lines.len() - MAX_OUTPUT_LINES
+        ));
+    }
+}
@@ -0,0 +1,65 @@
+use std::path::Path;
+use std::process::Stdio;
+
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use crate::config::Config;
+use crate::shell;
+
+/// Commands from `[preprocessors]` in the config, matched by glob.
+pub struct Preprocessors {
+    rules: Vec<(Gitignore, String)>,
+}
+
+impl Preprocessors {
+    pub fn new(root: &Path, config: &Config) -> Result<Self> {
+        let mut rules = Vec::new();
+        for (glob, command) in &config.preprocessors {
+            let mut builder = GitignoreBuilder::new(root);
+            builder
+                .add_line(None, glob)
+                .with_context(|| format!("invalid glob {glob} in [preprocessors]"))?;
+            rules.push((builder.build()?, command.clone()));
+        }
+        Ok(Preprocessors { rules })
+    }
+
+    /// The command for a file, from the first glob (in sorted order) that matches it.
+    pub fn command_for(&self, relative_path: &str) -> Option<&str> {
+        self.rules
+            .iter()
+            .find(|(glob, _)| {
+                glob.matched_path_or_any_parents(relative_path, false)
+                    .is_ignore()
+            })
+            .map(|(_, command)| command.as_str())
+    }
+}
+
+/// Runs `command` through the shell in `root`, with the file's bytes on stdin and its path in
+/// `PROMPTKIT_FILE`, and returns its stdout. Fails with the exit status and stderr if the
+/// command does not succeed.
+pub fn run(command: &str, root: &Path, path: &Path, data: &[u8]) -> Result<Vec<u8>, String> {
+    let output = shell::run(
+        shell::command(command)
+            .current_dir(root)
+            .env("PROMPTKIT_FILE", path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped()),
+        |stdin| {
+            // A command that does not read its input closes the pipe early; that is fine.
+            let _ = stdin.write_all(data);
+        },
+    )
+    .map_err(|err| format!("failed to run `{command}`: {err}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "`{command}` exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
@@ -0,0 +1,180 @@
+use std::fs;
+use std::path::Path;
+
+use clap::ValueEnum;
+
+/// Curated sets of `--exclude` globs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// Tests, generated files, and docs, data and assets: only source code remains.
+    CodeOnly,
+    /// Test files and directories, fixtures and snapshots.
+    NoTests,
+    /// Lockfiles, generated protobuf/OpenAPI code, build output and minified assets.
+    NoGenerated,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Ecosystem {
+    Rust,
+    JavaScript,
+    Python,
+    Go,
+    Jvm,
+}
+
+/// Files whose presence at the root (or one directory down) marks an ecosystem.
+const MARKERS: [(&str, Ecosystem); 10] = [
+    ("Cargo.toml", Ecosystem::Rust),
+    ("package.json", Ecosystem::JavaScript),
+    ("pyproject.toml", Ecosystem::Python),
+    ("setup.py", Ecosystem::Python),
+    ("requirements.txt", Ecosystem::Python),
+    ("go.mod", Ecosystem::Go),
+    ("pom.xml", Ecosystem::Jvm),
+    ("build.gradle", Ecosystem::Jvm),
+    ("build.gradle.kts", Ecosystem::Jvm),
+    ("settings.gradle.kts", Ecosystem::Jvm),
+];
+
+/// Globs that apply everywhere (`None`) or to one ecosystem.
+type Globs = &'static [(Option<Ecosystem>, &'static str)];
+
+const TESTS: Globs = &[
+    (None, "test/"),
+    (None, "tests/"),
+    (None, "__tests__/"),
+    (None, "testdata/"),
+    (None, "fixtures/"),
+    (None, "__fixtures__/"),
+    (None, "__snapshots__/"),
+    (None, "snapshots/"),
+    (None, "*.snap"),
+    (Some(Ecosystem::Rust), "*.pending-snap"),
+    (Some(Ecosystem::JavaScript), "spec/"),
+    (Some(Ecosystem::JavaScript), "*.test.*"),
+    (Some(Ecosystem::JavaScript), "*.spec.*"),
+    (Some(Ecosystem::JavaScript), "cypress/"),
+    (Some(Ecosystem::JavaScript), "e2e/"),
+    (Some(Ecosystem::Python), "test_*.py"),
+    (Some(Ecosystem::Python), "*_test.py"),
+    (Some(Ecosystem::Python), "conftest.py"),
+    (Some(Ecosystem::Go), "*_test.go"),
+    (Some(Ecosystem::Jvm), "src/test/"),
+    (Some(Ecosystem::Jvm), "*Test.java"),
+    (Some(Ecosystem::Jvm), "*Test.kt"),
+];
+
+const GENERATED: Globs = &[
+    (None, "*.min.js"),
+    (None, "*.min.css"),
+    (None, "*.map"),
+    (None, "dist/"),
+    (None, "generated/"),
+    (None, "*.generated.*"),
+    (None, "*_generated.*"),
+    (Some(Ecosystem::Rust), "Cargo.lock"),
+    (Some(Ecosystem::JavaScript), "package-lock.json"),
+    (Some(Ecosystem::JavaScript), "yarn.lock"),
+    (Some(Ecosystem::JavaScript), "pnpm-lock.yaml"),
+    (Some(Ecosystem::JavaScript), "bun.lock"),
+    (Some(Ecosystem::JavaScript), "*_pb.js"),
+    (Some(Ecosystem::JavaScript), "*_pb.d.ts"),
+    (Some(Ecosystem::JavaScript), ".next/"),
+    (Some(Ecosystem::Python), "poetry.lock"),
+    (Some(Ecosystem::Python), "uv.lock"),
+    (Some(Ecosystem::Python), "Pipfile.lock"),
+    (Some(Ecosystem::Python), "*_pb2.py"),
+    (Some(Ecosystem::Python), "*_pb2.pyi"),
+    (Some(Ecosystem::Python), "*_pb2_grpc.py"),
+    (Some(Ecosystem::Go), "go.sum"),
+    (Some(Ecosystem::Go), "*.pb.go"),
+    (Some(Ecosystem::Go), "*.pb.gw.go"),
+    (Some(Ecosystem::Go), "zz_generated*.go"),
+    (Some(Ecosystem::Go), "*_gen.go"),
+    (Some(Ecosystem::Jvm), "build/"),
+    (Some(Ecosystem::Jvm), "gradle/wrapper/"),
+    (Some(Ecosystem::Jvm), "gradlew*"),
+];
+
+/// Docs, data and assets, left out by `code-only` on top of tests and generated files.
+const NON_CODE: Globs = &[
+    (None, "docs/"),
+    (None, "*.md"),
+    (None, "*.mdx"),
+    (None, "*.rst"),
+    (None, "*.txt"),
+    (None, "LICENSE*"),
+    (None, "*.csv"),
+    (None, "*.tsv"),
+    (None, "*.svg"),
+    (None, "*.lock"),
+];
+
+/// The exclusion globs of `presets` for the ecosystems detected under `root`.
+pub fn globs(root: &Path, presets: &[Preset]) -> Vec<String> {
+    if presets.is_empty() {
+        return Vec::new();
+    }
+    select(&detect(root), presets)
+}
+
+/// [`globs`] for files that are not on disk, such as an archive's, given by their paths.
+pub fn globs_for_paths(paths: &[String], presets: &[Preset]) -> Vec<String> {
+    if presets.is_empty() {
+        return Vec::new();
+    }
+    select(&detect_paths(paths), presets)
+}
+
+fn select(ecosystems: &[Ecosystem], presets: &[Preset]) -> Vec<String> {
+    let mut sets = Vec::new();
+    for preset in presets {
+        match preset {
+            Preset::CodeOnly => sets.extend([TESTS, GENERATED, NON_CODE]),
+            Preset::NoTests => sets.push(TESTS),
+            Preset::NoGenerated => sets.push(GENERATED),
+        }
+    }
+    let mut globs: Vec<String> = sets
+        .into_iter()
+        .flatten()
+        .filter(|(ecosystem, _)| ecosystem.is_none_or(|ecosystem| ecosystems.contains(&ecosystem)))
+        .map(|(_, glob)| glob.to_string())
+        .collect();
+    globs.sort();
+    globs.dedup();
+    globs
+}
+
+/// Ecosystems with a marker file in `root` or a directory right under it.
+fn detect(root: &Path) -> Vec<Ecosystem> {
+    let mut dirs = vec![root.to_path_buf()];
+    if let Ok(entries) = fs::read_dir(root) {
+        dirs.extend(
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir()),
+        );
+    }
+    MARKERS
+        .iter()
+        .filter(|(marker, _)| dirs.iter().any(|dir| dir.join(marker).is_file()))
+        .map(|&(_, ecosystem)| ecosystem)
+        .collect()
+}
+
+/// [`detect`] over relative paths.
+fn detect_paths(paths: &[String]) -> Vec<Ecosystem> {
+    MARKERS
+        .iter()
+        .filter(|(marker, _)| {
+            paths.iter().any(|path| match path.split_once('/') {
+                Some((_, rest)) => rest == *marker,
+                None => path == marker,
+            })
+        })
+        .map(|&(_, ecosystem)| ecosystem)
+        .collect()
+}
@@ -0,0 +1,48 @@
+use std::collections::BTreeMap;
+
+/// Input prices in USD per million tokens, matched by longest model-name prefix.
+const BUILTIN_INPUT_PRICES: [(&str, f64); 22] = [
+    ("gpt-5", 1.25),
+    ("gpt-5-mini", 0.25),
+    ("gpt-5-nano", 0.05),
+    ("gpt-4.1", 2.00),
+    ("gpt-4.1-mini", 0.40),
+    ("gpt-4.1-nano", 0.10),
+    ("gpt-4o", 2.50),
+    ("gpt-4o-mini", 0.15),
+    ("o1", 15.00),
+    ("o3", 2.00),
+    ("o3-mini", 1.10),
+    ("o4-mini", 1.10),
+    ("claude-opus-4-5", 5.00),
+    ("claude-opus", 15.00),
+    ("claude-sonnet", 3.00),
+    ("claude-haiku-4-5", 1.00),
+    ("claude-haiku", 0.80),
+    ("claude-3-5-haiku", 0.80),
+    ("claude-3-7-sonnet", 3.00),
+    ("gemini-2.5-pro", 1.25),
+    ("gemini-2.5-flash", 0.30),
+    ("gemini-2.5-flash-lite", 0.10),
+];
+
+/// Estimated input cost in USD, or `None` if the model has no known price.
+///
+/// `overrides` (USD per million tokens, from the config) win over the built-in table.
+pub fn input_cost(tokens: usize, model: &str, overrides: &BTreeMap<String, f64>) -> Option<f64> {
+    let price = longest_prefix(
+        model,
+        overrides
+            .iter()
+            .map(|(prefix, price)| (prefix.as_str(), *price)),
+    )
+    .or_else(|| longest_prefix(model, BUILTIN_INPUT_PRICES.into_iter()))?;
+    Some(tokens as f64 * price / 1_000_000.0)
+}
+
+fn longest_prefix<'a>(model: &str, prices: impl Iterator<Item = (&'a str, f64)>) -> Option<f64> {
+    prices
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, price)| price)
+}
@@ -0,0 +1,148 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::with_thousands;
+
+/// Phases shorter than this are never shown, so small repositories print nothing.
+const DELAY: Duration = Duration::from_millis(300);
+const INTERVAL: Duration = Duration::from_millis(100);
+
+static PROGRESS: OnceLock<Progress> = OnceLock::new();
+
+/// A one-line status on stderr, redrawn in place while a phase runs.
+#[derive(Default)]
+struct Progress {
+    phase: Mutex<Option<(Phase, Instant)>>,
+    scanned: AtomicUsize,
+    included: AtomicUsize,
+    skipped: AtomicUsize,
+    bytes: AtomicU64,
+    counted: AtomicUsize,
+    /// Whether the line is currently on screen and must be cleared.
+    shown: AtomicBool,
+}
+
+#[derive(Clone, Copy)]
+pub enum Phase {
+    Collecting,
+    /// Tokenizing this many files.
+    Counting(usize),
+    /// Tokenizing the rendered prompt.
+    Rendering,
+}
+
+/// Shows progress for the rest of the run if stderr is a terminal.
+pub fn enable() {
+    if !io::stderr().is_terminal() {
+        return;
+    }
+    if PROGRESS.set(Progress::default()).is_err() {
+        return;
+    }
+    thread::spawn(|| {
+        loop {
+            thread::sleep(INTERVAL);
+            if let Some(progress) = PROGRESS.get() {
+                progress.draw();
+            }
+        }
+    });
+}
+
+/// Starts a phase, resetting its counters.
+pub fn start(phase: Phase) {
+    let Some(progress) = PROGRESS.get() else {
+        return;
+    };
+    for counter in [
+        &progress.scanned,
+        &progress.included,
+        &progress.skipped,
+        &progress.counted,
+    ] {
+        counter.store(0, Ordering::Relaxed);
+    }
+    progress.bytes.store(0, Ordering::Relaxed);
+    *progress.phase.lock().expect("progress lock") = Some((phase, Instant::now()));
+}
+
+/// Ends the current phase and clears the line, before anything else is written to stderr.
+pub fn finish() {
+    let Some(progress) = PROGRESS.get() else {
+        return;
+    };
+    let mut phase = progress.phase.lock().expect("progress lock");
+    *phase = None;
+    if progress.shown.swap(false, Ordering::Relaxed) {
+        eprint!("\r\x1b[K");
+    }
+}
+
+pub fn scanned() {
+    if let Some(progress) = PROGRESS.get() {
+        progress.scanned.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn included(bytes: usize) {
+    if let Some(progress) = PROGRESS.get() {
+        progress.included.fetch_add(1, Ordering::Relaxed);
+        progress.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+pub fn skipped() {
+    if let Some(progress) = PROGRESS.get() {
+        progress.skipped.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn counted() {
+    if let Some(progress) = PROGRESS.get() {
+        progress.counted.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Progress {
+    fn draw(&self) {
+        // Holding the lock keeps `finish` from clearing the line halfway through a redraw.
+        let phase = self.phase.lock().expect("progress lock");
+        let Some((phase, started)) = *phase else {
+            return;
+        };
+        if started.elapsed() < DELAY {
+            return;
+        }
+        let load = |counter: &AtomicUsize| with_thousands(counter.load(Ordering::Relaxed));
+        let line = match phase {
+            Phase::Collecting => format!(
+                "Collecting: {} files scanned, {} included, {} skipped, {} read",
+                load(&self.scanned),
+                load(&self.included),
+                load(&self.skipped),
+                human_bytes(self.bytes.load(Ordering::Relaxed))
+            ),
+            Phase::Counting(total) => format!(
+                "Counting tokens: {}/{} files",
+                load(&self.counted),
+                with_thousands(total)
+            ),
+            Phase::Rendering => "Counting prompt tokens...".to_string(),
+        };
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[K{line}");
+        let _ = stderr.flush();
+        self.shown.store(true, Ordering::Relaxed);
+    }
+}
+
+fn human_bytes(bytes: u64) -> String {
+    match bytes {
+        0..1_000_000 => format!("{:.1} kB", bytes as f64 / 1e3),
+        1_000_000..1_000_000_000 => format!("{:.1} MB", bytes as f64 / 1e6),
+        _ => format!("{:.1} GB", bytes as f64 / 1e9),
+    }
+}
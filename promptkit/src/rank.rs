@@ -0,0 +1,73 @@
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::FileDump;
+
+const STOPWORDS: [&str; 24] = [
+    "the", "and", "for", "with", "that", "this", "from", "into", "are", "was", "but", "not", "you",
+    "all", "can", "how", "why", "what", "when", "make", "use", "please", "code", "file",
+];
+
+/// Order in which files appear in the dump (and are trimmed from the end under a budget).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Order {
+    /// Sorted by relative path.
+    Path,
+    /// Depth-first in the order of the file tree: `foo/` and its files before `foo.rs`.
+    Tree,
+    /// Most relevant to the task first.
+    Relevance,
+    /// Smallest first.
+    Size,
+}
+
+pub fn sort(files: &mut [FileDump], order: Order, task: &str) {
+    match order {
+        Order::Path => files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path)),
+        // `Path` compares component by component, as the tree is laid out.
+        Order::Tree => {
+            files.sort_by(|a, b| Path::new(&a.relative_path).cmp(Path::new(&b.relative_path)))
+        }
+        Order::Size => files.sort_by(|a, b| {
+            a.contents
+                .len()
+                .cmp(&b.contents.len())
+                .then_with(|| a.relative_path.cmp(&b.relative_path))
+        }),
+        Order::Relevance => {
+            let terms = terms(task);
+            // Scores are scaled to integers so ties fall back to path order deterministically.
+            files.sort_by_cached_key(|file| {
+                (
+                    Reverse((score(file, &terms) * 1000.0) as u64),
+                    file.relative_path.clone(),
+                )
+            });
+        }
+    }
+}
+
+fn terms(task: &str) -> BTreeSet<String> {
+    task.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .map(str::to_lowercase)
+        .filter(|term| term.chars().count() >= 3 && !STOPWORDS.contains(&term.as_str()))
+        .collect()
+}
+
+/// Path matches weigh heavily; content matches add a damped term frequency.
+fn score(file: &FileDump, terms: &BTreeSet<String>) -> f64 {
+    let path = file.relative_path.to_lowercase();
+    let contents = file.contents.to_lowercase();
+
+    terms
+        .iter()
+        .map(|term| {
+            let path_hits = path.matches(term.as_str()).count() as f64;
+            let content_hits = contents.matches(term.as_str()).count() as f64;
+            5.0 * path_hits + content_hits.ln_1p()
+        })
+        .sum()
+}
@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use ignore::DirEntry;
+
+use crate::git;
+
+/// How `--since` decides when a file last changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Source {
+    /// The modification time on disk.
+    Mtime,
+    /// The commits that touched it, and whether it has uncommitted changes.
+    Git,
+}
+
+/// Keeps the files changed since a point in time.
+pub struct Recency {
+    since: SystemTime,
+    /// Paths relative to the root, for [`Source::Git`].
+    changed: Option<HashSet<String>>,
+}
+
+impl Recency {
+    pub fn new(root: &Path, since: SystemTime, source: Source) -> Result<Self> {
+        let changed = match source {
+            Source::Mtime => None,
+            Source::Git => Some(changed_since(root, since)?),
+        };
+        Ok(Recency { since, changed })
+    }
+
+    pub fn matches(&self, relative_path: &str, dir_entry: &DirEntry) -> bool {
+        match &self.changed {
+            Some(changed) => changed.contains(relative_path),
+            None => dir_entry
+                .metadata()
+                .ok()
+                .and_then(|metadata| metadata.modified().ok())
+                .is_some_and(|modified| modified >= self.since),
+        }
+    }
+}
+
+/// Paths under `root` touched by a commit since `since`, or with uncommitted changes, or untracked.
+fn changed_since(root: &Path, since: SystemTime) -> Result<HashSet<String>> {
+    git::run(root, &["rev-parse", "--is-inside-work-tree"])
+        .context("--since-source git needs a Git work tree")?;
+    let secs = since
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut changed: HashSet<String> = git::run(
+        root,
+        &[
+            "log",
+            &format!("--since=@{secs}"),
+            "--relative",
+            "--name-only",
+            "--format=",
+        ],
+    )?
+    .lines()
+    .filter(|line| !line.is_empty())
+    .map(str::to_string)
+    .collect();
+    // Fails in a repository without commits, where everything is untracked anyway.
+    if let Ok(diff) = git::run(root, &["diff", "HEAD", "--relative", "--name-only"]) {
+        changed.extend(diff.lines().map(str::to_string));
+    }
+    let untracked = git::run(root, &["ls-files", "--others", "--exclude-standard"])?;
+    changed.extend(untracked.lines().map(str::to_string));
+    Ok(changed)
+}
+
+/// Parses a `--since` value: a duration before now like `30m`, `12h`, `7d`, `2w` or `1d12h`,
+/// or a UTC date like `2026-01-31` or `2026-01-31 09:30` (also with `T`, seconds and `Z`).
+pub fn parse_since(arg: &str) -> Result<SystemTime, String> {
+    let arg = arg.trim();
+    if let Some(ago) = parse_duration(arg) {
+        return SystemTime::now()
+            .checked_sub(ago)
+            .ok_or_else(|| format!("{arg:?} is too long ago"));
+    }
+    parse_timestamp(arg).ok_or_else(|| {
+        format!("invalid time {arg:?}; expected e.g. 7d, 12h, 2026-01-31 or 2026-01-31 09:30")
+    })
+}
+
+/// `7d`, `1d12h`, ... with `s`, `m`, `h`, `d` and `w` units.
+fn parse_duration(arg: &str) -> Option<Duration> {
+    let mut secs: u64 = 0;
+    let mut rest = arg;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+        let count: u64 = rest[..digits].parse().ok()?;
+        let unit = match rest[digits..].chars().next()? {
+            's' => 1,
+            'm' => 60,
+            'h' => 3_600,
+            'd' => 86_400,
+            'w' => 7 * 86_400,
+            _ => return None,
+        };
+        secs = secs.checked_add(count.checked_mul(unit)?)?;
+        rest = &rest[digits + 1..];
+    }
+    (!arg.is_empty()).then_some(Duration::from_secs(secs))
+}
+
+/// `YYYY-MM-DD[( |T)HH:MM[:SS]][Z]`, in UTC.
+fn parse_timestamp(arg: &str) -> Option<SystemTime> {
+    let arg = arg.strip_suffix('Z').unwrap_or(arg);
+    let (date, time) = match arg.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (arg, None),
+    };
+    let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let mut secs = days_from_civil(year, month, day) * 86_400;
+    if let Some(time) = time {
+        let parts: Vec<i64> = time
+            .split(':')
+            .map(|part| part.parse().ok())
+            .collect::<Option<_>>()?;
+        let (hour, minute, second) = match parts[..] {
+            [hour, minute] => (hour, minute, 0),
+            [hour, minute, second] => (hour, minute, second),
+            _ => return None,
+        };
+        if !(0..24).contains(&hour) || !(0..60).contains(&minute) || !(0..60).contains(&second) {
+            return None;
+        }
+        secs += hour * 3_600 + minute * 60 + second;
+    }
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(secs).ok()?))
+}
+
+/// Days since the Unix epoch, after Howard Hinnant's `days_from_civil`.
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
@@ -0,0 +1,138 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+/// A secret pattern. If the regex has a `secret` capture group, only that group is
+/// replaced; otherwise the whole match is.
+struct Rule {
+    kind: &'static str,
+    regex: Regex,
+}
+
+impl Rule {
+    fn new(kind: &'static str, pattern: &str) -> Self {
+        Self {
+            kind,
+            regex: Regex::new(pattern).expect("invalid redaction pattern"),
+        }
+    }
+}
+
+static RULES: LazyLock<Vec<Rule>> = LazyLock::new(|| {
+    vec![
+        Rule::new(
+            "private-key",
+            r"-----BEGIN[A-Z ]*PRIVATE KEY( BLOCK)?-----[\s\S]*?-----END[A-Z ]*PRIVATE KEY( BLOCK)?-----",
+        ),
+        Rule::new("aws-access-key-id", r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b"),
+        Rule::new(
+            "aws-secret-access-key",
+            r#"(?i)aws_?secret_?access_?key["']?\s*[:=]\s*["']?(?P<secret>[A-Za-z0-9/+=]{40})(?:["'\s,;]|$)"#,
+        ),
+        Rule::new(
+            "github-token",
+            r"\b(?:gh[pousr]_[A-Za-z0-9]{36,}|github_pat_[A-Za-z0-9_]{22,})\b",
+        ),
+        Rule::new("anthropic-api-key", r"\bsk-ant-[A-Za-z0-9_-]{20,}"),
+        Rule::new("openai-api-key", r"\bsk-(?:proj-)?[A-Za-z0-9_-]{20,}"),
+        Rule::new("slack-token", r"\bxox[abprs]-[A-Za-z0-9-]{10,}"),
+        Rule::new("google-api-key", r"\bAIza[0-9A-Za-z_-]{35}\b"),
+        Rule::new("stripe-key", r"\b(?:sk|rk)_live_[0-9A-Za-z]{24,}\b"),
+    ]
+});
+
+/// `KEY=value` lines in `.env`-style files, where the key looks like it holds a secret.
+static ENV_ASSIGNMENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?im)^[ \t]*(?:export[ \t]+)?[A-Z0-9_]*(?:SECRET|TOKEN|PASSWORD|PASSWD|API_?KEY|ACCESS_?KEY|PRIVATE_?KEY|CREDENTIALS?)[A-Z0-9_]*[ \t]*=[ \t]*["']?(?P<secret>[^\s"'#]+)"#,
+    )
+    .expect("invalid env assignment pattern")
+});
+
+pub struct Redaction {
+    pub relative_path: String,
+    pub kind: &'static str,
+    pub line: usize,
+}
+
+impl fmt::Display for Redaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "path={}, kind={}, line={}",
+            self.relative_path, self.kind, self.line
+        )
+    }
+}
+
+/// Replaces secrets in `contents` with `[REDACTED:<kind>]`.
+///
+/// Returns `None` if nothing was found, so callers can keep the original string.
+pub fn redact(relative_path: &str, contents: &str) -> Option<(String, Vec<Redaction>)> {
+    let mut spans: Vec<(usize, usize, &'static str)> = Vec::new();
+
+    for rule in RULES.iter() {
+        collect_spans(&rule.regex, rule.kind, contents, &mut spans);
+    }
+    if is_env_file(relative_path) {
+        collect_spans(&ENV_ASSIGNMENT, "env-secret", contents, &mut spans);
+    }
+
+    if spans.is_empty() {
+        return None;
+    }
+
+    spans.sort_by_key(|&(start, _, _)| start);
+
+    let mut redacted = String::with_capacity(contents.len());
+    let mut redactions = Vec::new();
+    let mut cursor = 0;
+    for (start, end, kind) in spans {
+        redacted.push_str(&contents[cursor..start]);
+        redacted.push_str("[REDACTED:");
+        redacted.push_str(kind);
+        redacted.push(']');
+        redactions.push(Redaction {
+            relative_path: relative_path.to_string(),
+            kind,
+            line: contents[..start].matches('\n').count() + 1,
+        });
+        cursor = end;
+    }
+    redacted.push_str(&contents[cursor..]);
+
+    Some((redacted, redactions))
+}
+
+fn collect_spans(
+    regex: &Regex,
+    kind: &'static str,
+    contents: &str,
+    spans: &mut Vec<(usize, usize, &'static str)>,
+) {
+    for captures in regex.captures_iter(contents) {
+        let found = captures
+            .name("secret")
+            .or_else(|| captures.get(0))
+            .expect("capture group 0 always matches");
+        // Earlier rules win when spans overlap (e.g. a key inside a `.env` assignment).
+        if spans
+            .iter()
+            .all(|&(start, end, _)| found.end() <= start || found.start() >= end)
+        {
+            spans.push((found.start(), found.end(), kind));
+        }
+    }
+}
+
+fn is_env_file(relative_path: &str) -> bool {
+    let Some(name) = Path::new(relative_path)
+        .file_name()
+        .and_then(|name| name.to_str())
+    else {
+        return false;
+    };
+    name == ".env" || name.starts_with(".env.") || name.ends_with(".env")
+}
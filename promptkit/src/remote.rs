@@ -0,0 +1,69 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use tempfile::TempDir;
+
+use crate::git;
+
+/// A shallow checkout of a remote repository, deleted when dropped.
+pub struct Checkout {
+    dir: TempDir,
+    /// `url[@ref]` as given by the user, shown as the prompt root.
+    pub label: String,
+}
+
+impl Checkout {
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+/// Fetches `url[@ref]` at depth 1 into a temporary directory.
+///
+/// Fetching by ref (rather than `git clone --branch`) also works for commit SHAs.
+pub fn fetch(spec: &str) -> Result<Checkout> {
+    let (url, reference) = split_ref(spec);
+    // Git takes a leading `-` as an option; `--` below guards the arguments as well.
+    if url.starts_with('-') || reference.is_some_and(|reference| reference.starts_with('-')) {
+        bail!("invalid repository {spec}: URLs and refs cannot start with '-'");
+    }
+    let dir = tempfile::Builder::new()
+        .prefix("promptkit-")
+        .tempdir()
+        .context("failed to create temporary directory")?;
+    let path = dir.path();
+
+    eprintln!("Fetching {}...", spec);
+    git::run(path, &["init", "--quiet"])?;
+    git::run(path, &["remote", "add", "--", "origin", url])?;
+    git::run(
+        path,
+        &[
+            "fetch",
+            "--quiet",
+            "--depth",
+            "1",
+            "--",
+            "origin",
+            reference.unwrap_or("HEAD"),
+        ],
+    )
+    .with_context(|| format!("failed to fetch {}", spec))?;
+    git::run(path, &["checkout", "--quiet", "FETCH_HEAD"])?;
+
+    Ok(Checkout {
+        dir,
+        label: spec.to_string(),
+    })
+}
+
+/// Splits a trailing `@ref`, leaving `git@host:` style user prefixes alone.
+fn split_ref(spec: &str) -> (&str, Option<&str>) {
+    if let Some((url, reference)) = spec.rsplit_once('@') {
+        let location = url.split_once("://").map_or(url, |(_, rest)| rest);
+        if location.contains(['/', ':']) && !reference.is_empty() {
+            return (url, Some(reference));
+        }
+    }
+    (spec, None)
+}
@@ -0,0 +1,53 @@
+use clap::ValueEnum;
+
+/// How the model is asked to format its answer, in a section after the task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ResponseFormat {
+    /// Unified diffs against the files above, and nothing else.
+    Diff,
+    /// Full contents of each changed or new file in a fenced block headed by its path.
+    Files,
+    /// A single JSON object listing the changed files and their new contents.
+    Json,
+    /// Prose, with code quoted by path.
+    Freeform,
+}
+
+impl ResponseFormat {
+    pub fn instructions(self) -> &'static str {
+        match self {
+            ResponseFormat::Diff => {
+                "Reply only with unified diffs that apply with `git apply` from the root of the \
+repository, in a single ```diff fenced block:
+- Start each file with `diff --git a/<path> b/<path>`, then `--- a/<path>` and `+++ b/<path>` \
+(`/dev/null` for new or deleted files), using the paths shown above.
+- Give each hunk an exact `@@ -start,count +start,count @@` header and at least 3 lines of \
+unchanged context, copied exactly from the files above.
+- Do not include explanations, or files that do not change."
+            }
+            ResponseFormat::Files => {
+                "Reply with the full new contents of every file you change or create, and nothing \
+else:
+- Put each file under a `### <path>` heading, using the paths shown above, followed by one \
+fenced code block with its complete contents. Never elide parts with comments such as \
+`// ... rest unchanged`.
+- To delete a file, write its heading followed by the line `[deleted]` instead of a code block.
+- Do not include files that do not change."
+            }
+            ResponseFormat::Json => {
+                "Reply with a single JSON object and nothing else (no code fence, no prose), of \
+the form:
+{\"summary\": \"<one paragraph>\", \"files\": [{\"path\": \"<path>\", \"action\": \
+\"modify\" | \"create\" | \"delete\", \"contents\": \"<complete new contents, or null to \
+delete>\"}]}
+- Use the paths shown above, and give complete file contents, never excerpts.
+- List only files that change."
+            }
+            ResponseFormat::Freeform => {
+                "Reply in Markdown prose. When you refer to code, name the file by the path \
+shown above (and the function or type), and quote only the lines that matter in fenced code \
+blocks."
+            }
+        }
+    }
+}
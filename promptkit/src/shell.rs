@@ -0,0 +1,31 @@
+use std::io::{self, Write};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+
+/// A command line run through the shell: `sh -c`, or `cmd /C` on Windows.
+pub fn command(command_line: &str) -> Command {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C");
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c");
+        command
+    };
+    command.arg(command_line);
+    command
+}
+
+/// Spawns `command` with what `input` writes on its stdin, and waits for it. Output that is
+/// not redirected elsewhere is captured.
+pub fn run(command: &mut Command, input: impl FnOnce(&mut dyn Write) + Send) -> io::Result<Output> {
+    let mut child = command.stdin(Stdio::piped()).spawn()?;
+    // Feed stdin from another thread so a command that writes before reading everything
+    // cannot deadlock on a full pipe.
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    thread::scope(|scope| {
+        scope.spawn(move || input(&mut stdin));
+        child.wait_with_output()
+    })
+}
@@ -0,0 +1,81 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+
+use crate::config;
+
+/// `<config dir>/promptkit/snippets`, where each snippet is a `<name>.md` file.
+fn dir() -> Result<PathBuf> {
+    config::config_dir()
+        .map(|dir| dir.join("snippets"))
+        .context("failed to determine the config directory")
+}
+
+fn path(name: &str) -> Result<PathBuf> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || "-_.".contains(c));
+    if !valid {
+        bail!("invalid snippet name {name:?}; use letters, digits, `-`, `_` and `.`");
+    }
+    Ok(dir()?.join(format!("{name}.md")))
+}
+
+/// Saves `text` as snippet `name` and returns its path. Fails if it exists, unless `force`.
+pub fn add(name: &str, text: &str, force: bool) -> Result<PathBuf> {
+    let path = path(name)?;
+    if text.trim().is_empty() {
+        bail!("snippet {name} is empty");
+    }
+    if !force && path.exists() {
+        bail!("snippet {name} already exists; use --force to replace it");
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create directory {}", dir.display()))?;
+    }
+    fs::write(&path, text).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+pub fn read(name: &str) -> Result<String> {
+    let path = path(name)?;
+    match fs::read_to_string(&path) {
+        Ok(text) => Ok(text),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            bail!("no snippet named {name}; see `promptkit snippet list`")
+        }
+        Err(err) => Err(err).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+/// Every saved snippet as `(name, text)`, sorted by name.
+pub fn list() -> Result<Vec<(String, String)>> {
+    let dir = dir()?;
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("failed to read {}", dir.display())),
+    };
+    let mut snippets = Vec::new();
+    for entry in entries {
+        let path = entry
+            .with_context(|| format!("failed to read {}", dir.display()))?
+            .path();
+        if let Some(name) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".md"))
+        {
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            snippets.push((name.to_string(), text));
+        }
+    }
+    snippets.sort();
+    Ok(snippets)
+}
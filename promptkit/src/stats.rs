@@ -0,0 +1,247 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use clap::ValueEnum;
+use rayon::prelude::*;
+use serde::Serialize;
+use tiktoken_rs::CoreBPE;
+
+use crate::{FileDump, SkippedFile, TOKENIZER_NAME};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum SortKey {
+    Tokens,
+    Bytes,
+    Files,
+    Name,
+}
+
+/// How `dump` reports its stats on stderr.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// `Skipped:`, `Part:`, ... lines and a closing `Stats:` line.
+    #[default]
+    Text,
+    /// A single JSON object (see [`Summary`]).
+    Json,
+}
+
+#[derive(Default, Serialize)]
+pub struct Totals {
+    files: usize,
+    bytes: usize,
+    tokens: usize,
+}
+
+impl Totals {
+    fn add(&mut self, bytes: usize, tokens: usize) {
+        self.files += 1;
+        self.bytes += bytes;
+        self.tokens += tokens;
+    }
+}
+
+pub struct Breakdown {
+    by_dir: BTreeMap<String, Totals>,
+    by_ext: BTreeMap<String, Totals>,
+    total: Totals,
+}
+
+impl Breakdown {
+    /// Groups files by their directory (truncated to `depth` components) and by extension.
+    pub fn new(files: &[FileDump], tokenizer: &CoreBPE, depth: usize) -> Self {
+        let mut breakdown = Breakdown {
+            by_dir: BTreeMap::new(),
+            by_ext: BTreeMap::new(),
+            total: Totals::default(),
+        };
+
+        let tokens: Vec<usize> = files
+            .par_iter()
+            .map(|file| crate::cache::count(tokenizer, file))
+            .collect();
+
+        for (file, tokens) in files.iter().zip(tokens) {
+            // Files left unread for their cached count are as large as on disk.
+            let bytes = file
+                .source
+                .as_ref()
+                .map_or(file.contents.len(), |source| source.size as usize);
+            let path = Path::new(&file.relative_path);
+
+            breakdown
+                .by_dir
+                .entry(dir_key(path, depth))
+                .or_default()
+                .add(bytes, tokens);
+            breakdown
+                .by_ext
+                .entry(ext_key(path))
+                .or_default()
+                .add(bytes, tokens);
+            breakdown.total.add(bytes, tokens);
+        }
+
+        breakdown
+    }
+
+    pub fn total_tokens(&self) -> usize {
+        self.total.tokens
+    }
+
+    pub fn render(&self, sort: SortKey, top: Option<usize>) -> String {
+        let mut out = String::new();
+        render_table(&mut out, "Directory", &self.by_dir, sort, top);
+        out.push('\n');
+        render_table(&mut out, "Extension", &self.by_ext, sort, top);
+        out.push('\n');
+        out.push_str(&format!(
+            "Total: tokens={}, files={}, bytes={}\n",
+            self.total.tokens, self.total.files, self.total.bytes
+        ));
+        out
+    }
+}
+
+pub fn dir_key(path: &Path, depth: usize) -> String {
+    let components: Vec<String> = path
+        .parent()
+        .into_iter()
+        .flat_map(|parent| parent.components())
+        .take(depth)
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    if components.is_empty() {
+        ".".to_string()
+    } else {
+        format!("{}/", components.join("/"))
+    }
+}
+
+fn ext_key(path: &Path) -> String {
+    path.extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_else(|| "(none)".to_string())
+}
+
+fn render_table(
+    out: &mut String,
+    label: &str,
+    rows: &BTreeMap<String, Totals>,
+    sort: SortKey,
+    top: Option<usize>,
+) {
+    let mut rows: Vec<(&String, &Totals)> = rows.iter().collect();
+    match sort {
+        SortKey::Tokens => rows.sort_by_key(|(_, totals)| Reverse(totals.tokens)),
+        SortKey::Bytes => rows.sort_by_key(|(_, totals)| Reverse(totals.bytes)),
+        SortKey::Files => rows.sort_by_key(|(_, totals)| Reverse(totals.files)),
+        SortKey::Name => {}
+    }
+    let shown = top.unwrap_or(rows.len()).min(rows.len());
+
+    out.push_str(&format!(
+        "{:>10}  {:>10}  {:>6}  {}\n",
+        "tokens", "bytes", "files", label
+    ));
+    for (name, totals) in &rows[..shown] {
+        out.push_str(&format!(
+            "{:>10}  {:>10}  {:>6}  {}\n",
+            totals.tokens, totals.bytes, totals.files, name
+        ));
+    }
+    if shown < rows.len() {
+        out.push_str(&format!("... {} more\n", rows.len() - shown));
+    }
+}
+
+/// Stats of a dump for `--stats-format json`. Fields keep their names and are always present
+/// (`null` when not applicable), so scripts can rely on them.
+#[derive(Serialize)]
+pub struct Summary<'a> {
+    pub tokenizer: &'static str,
+    /// Tokens of the whole prompt (summed over parts when chunked).
+    pub tokens: usize,
+    /// Tokens of file contents, each file tokenized on its own.
+    pub content_tokens: usize,
+    /// The rest of `tokens`: preamble, file tree, file headers and task.
+    pub overhead_tokens: usize,
+    /// The files with the most tokens, largest first.
+    pub top_files: Vec<FileTokens<'a>>,
+    /// Tokens of each part, with `--chunk-tokens`.
+    pub part_tokens: Option<&'a [usize]>,
+    pub files_included: usize,
+    pub files_skipped: usize,
+    pub files_truncated: usize,
+    /// Files replaced by a summary with `--summarize-over`.
+    pub files_summarized: usize,
+    /// Files left out as duplicates of another file.
+    pub files_deduplicated: usize,
+    /// Lockfiles and minified or vendored assets replaced by a summary.
+    pub files_condensed: usize,
+    /// Bytes of included file contents.
+    pub bytes: usize,
+    /// Tokens saved by `--strip`.
+    pub tokens_stripped: Option<usize>,
+    pub model: Option<&'a str>,
+    /// Estimated input cost in USD, if `model` has a known price.
+    pub input_cost: Option<f64>,
+    /// Number of skipped files per reason (`too_large`, `non_utf8`, ...).
+    pub skip_reasons: BTreeMap<&'static str, usize>,
+    /// Included files, bytes and tokens per extension (`(none)` for files without one).
+    pub extensions: BTreeMap<String, Totals>,
+}
+
+#[derive(Serialize)]
+pub struct FileTokens<'a> {
+    pub path: &'a str,
+    pub tokens: usize,
+}
+
+impl<'a> Summary<'a> {
+    /// Everything but the prompt-level fields, which start out empty.
+    pub fn new(files: &[FileDump], skipped: &[SkippedFile], file_tokens: &[usize]) -> Self {
+        let mut skip_reasons = BTreeMap::new();
+        for file in skipped {
+            *skip_reasons.entry(file.reason.kind()).or_default() += 1;
+        }
+        let mut extensions: BTreeMap<String, Totals> = BTreeMap::new();
+        for (file, &tokens) in files.iter().zip(file_tokens) {
+            extensions
+                .entry(ext_key(Path::new(&file.relative_path)))
+                .or_default()
+                .add(file.contents.len(), tokens);
+        }
+
+        Summary {
+            tokenizer: TOKENIZER_NAME,
+            tokens: 0,
+            content_tokens: 0,
+            overhead_tokens: 0,
+            top_files: Vec::new(),
+            part_tokens: None,
+            files_included: files.len(),
+            files_skipped: skipped.len(),
+            files_truncated: files
+                .iter()
+                .filter(|file| file.truncated_from.is_some())
+                .count(),
+            files_summarized: files
+                .iter()
+                .filter(|file| file.summarized_from.is_some())
+                .count(),
+            files_deduplicated: files
+                .iter()
+                .filter(|file| file.duplicate_of.is_some())
+                .count(),
+            files_condensed: files.iter().filter(|file| file.condensed.is_some()).count(),
+            bytes: files.iter().map(|file| file.contents.len()).sum(),
+            tokens_stripped: None,
+            model: None,
+            input_cost: None,
+            skip_reasons,
+            extensions,
+        }
+    }
+}
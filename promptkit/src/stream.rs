@@ -0,0 +1,97 @@
+use std::fmt;
+use std::hash::{DefaultHasher, Hasher};
+use std::io::{self, Write};
+
+use tiktoken_rs::CoreBPE;
+
+/// Rendered text held back before it is tokenized and written out.
+const BLOCK: usize = 1 << 20;
+
+/// Passes rendered prompt text through to a writer in blocks, counting its tokens on the way,
+/// so that a prompt never has to be held in memory as a whole.
+///
+/// Blocks end right after a blank line that precedes a `#` heading, where BPE pre-tokenization
+/// always breaks (see [`crate::count_tokens`]), so the count matches tokenizing the whole text.
+pub struct TokenStream<'a, W: Write> {
+    out: W,
+    tokenizer: Option<&'a CoreBPE>,
+    buffer: String,
+    tokens: usize,
+    /// The first write error, which `fmt::Write` can only report as `fmt::Error`.
+    error: Option<io::Error>,
+}
+
+impl<'a, W: Write> TokenStream<'a, W> {
+    /// Counts tokens with `tokenizer`, or only passes the text through if it is `None`.
+    pub fn new(out: W, tokenizer: Option<&'a CoreBPE>) -> Self {
+        TokenStream {
+            out,
+            tokenizer,
+            buffer: String::new(),
+            tokens: 0,
+            error: None,
+        }
+    }
+
+    /// Writes out what is left and returns the token count, or the first write error.
+    pub fn finish(mut self) -> io::Result<usize> {
+        if self.error.is_none() {
+            self.emit(self.buffer.len());
+        }
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        self.out.flush()?;
+        Ok(self.tokens)
+    }
+
+    fn emit(&mut self, end: usize) {
+        let block = &self.buffer[..end];
+        if let Some(tokenizer) = self.tokenizer {
+            self.tokens += crate::count_tokens(tokenizer, block);
+        }
+        if let Err(err) = self.out.write_all(block.as_bytes()) {
+            self.error = Some(err);
+        }
+        self.buffer.drain(..end);
+    }
+}
+
+impl<W: Write> fmt::Write for TokenStream<'_, W> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        if self.error.is_some() {
+            return Err(fmt::Error);
+        }
+        self.buffer.push_str(text);
+        if self.buffer.len() >= BLOCK
+            && let Some(idx) = self.buffer.rfind("\n\n#")
+        {
+            self.emit(idx + 2);
+        }
+        match self.error {
+            Some(_) => Err(fmt::Error),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Hashes the text written to it, to compare prompts without holding them.
+#[derive(Default)]
+pub struct Fingerprint(DefaultHasher);
+
+impl Fingerprint {
+    pub fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+}
+
+impl Write for Fingerprint {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
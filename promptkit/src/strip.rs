@@ -0,0 +1,287 @@
+use clap::ValueEnum;
+
+/// What `--strip` removes from file contents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Strip {
+    /// Comments, in languages with known comment syntax.
+    Comments,
+    /// Blank and whitespace-only lines.
+    BlankLines,
+    /// Both of the above.
+    Both,
+}
+
+impl Strip {
+    fn comments(self) -> bool {
+        matches!(self, Strip::Comments | Strip::Both)
+    }
+
+    fn blank_lines(self) -> bool {
+        matches!(self, Strip::BlankLines | Strip::Both)
+    }
+}
+
+/// Comment and string syntax of a language family.
+struct Syntax {
+    line_comment: &'static str,
+    block_comment: Option<(&'static str, &'static str)>,
+    /// Block comments nest (Rust).
+    nested_blocks: bool,
+    /// Line comments only start at the beginning of a word (shells: `$#`, `${#x}`).
+    word_start: bool,
+    /// Quotes of single-line strings with backslash escapes.
+    quotes: &'static [char],
+    /// Quotes of strings that may span lines, with backslash escapes (Rust `"`, JS templates).
+    multi_line_quotes: &'static [char],
+    /// Quotes of strings that may span lines, without escapes (Go raw strings).
+    raw_quotes: &'static [char],
+    /// Python's `"""` / `'''` strings.
+    triple_quotes: bool,
+    /// Rust's `'a` lifetimes vs `'a'` chars, and `r#"..."#` raw strings.
+    rust: bool,
+}
+
+const C_LIKE: Syntax = Syntax {
+    line_comment: "//",
+    block_comment: Some(("/*", "*/")),
+    nested_blocks: false,
+    word_start: false,
+    quotes: &['"', '\''],
+    multi_line_quotes: &[],
+    raw_quotes: &[],
+    triple_quotes: false,
+    rust: false,
+};
+
+const HASH: Syntax = Syntax {
+    line_comment: "#",
+    block_comment: None,
+    nested_blocks: false,
+    word_start: false,
+    quotes: &['"', '\''],
+    multi_line_quotes: &[],
+    raw_quotes: &[],
+    triple_quotes: false,
+    rust: false,
+};
+
+fn syntax(language: &str) -> Option<Syntax> {
+    Some(match language {
+        "rust" => Syntax {
+            nested_blocks: true,
+            quotes: &[],
+            multi_line_quotes: &['"'],
+            rust: true,
+            ..C_LIKE
+        },
+        "javascript" | "jsx" | "typescript" | "tsx" => Syntax {
+            multi_line_quotes: &['`'],
+            ..C_LIKE
+        },
+        "go" => Syntax {
+            raw_quotes: &['`'],
+            ..C_LIKE
+        },
+        "java" | "kotlin" | "scala" | "swift" | "c" | "cpp" | "csharp" | "objectivec" | "dart"
+        | "protobuf" => C_LIKE,
+        "python" => Syntax {
+            triple_quotes: true,
+            ..HASH
+        },
+        "bash" | "zsh" => Syntax {
+            word_start: true,
+            ..HASH
+        },
+        "ruby" | "perl" | "r" | "starlark" | "makefile" | "dockerfile" | "cmake" => HASH,
+        _ => return None,
+    })
+}
+
+/// Strips comments (if the language is known) and/or blank lines from `contents`.
+///
+/// Lines left empty by removing a comment are dropped, and trailing whitespace before a
+/// removed comment is trimmed. A leading `#!` line is kept.
+pub fn strip(contents: &str, language: Option<&str>, mode: Strip) -> String {
+    let syntax = language.filter(|_| mode.comments()).and_then(syntax);
+    let (text, commented) = match &syntax {
+        Some(syntax) => strip_comments(contents, syntax),
+        None => (contents.to_string(), Vec::new()),
+    };
+
+    let mut out = String::with_capacity(text.len());
+    for (idx, line) in text.split_inclusive('\n').enumerate() {
+        let body = line.trim_end_matches(['\n', '\r']);
+        let blank = body.trim().is_empty();
+        if commented.get(idx).copied().unwrap_or(false) {
+            if blank {
+                continue;
+            }
+            out.push_str(body.trim_end());
+            out.push_str(&line[body.len()..]);
+            continue;
+        }
+        if blank && mode.blank_lines() {
+            continue;
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+/// Removes comments, returning the text and, per output line, whether a comment was removed.
+fn strip_comments(contents: &str, syntax: &Syntax) -> (String, Vec<bool>) {
+    let chars: Vec<char> = contents.chars().collect();
+    let mut out = String::with_capacity(contents.len());
+    let mut commented = vec![false];
+    let mut i = 0;
+
+    let starts_with = |i: usize, token: &str| {
+        token
+            .chars()
+            .enumerate()
+            .all(|(offset, c)| chars.get(i + offset) == Some(&c))
+    };
+
+    if contents.starts_with("#!") {
+        while i < chars.len() && chars[i] != '\n' {
+            out.push(chars[i]);
+            i += 1;
+        }
+    }
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        let word_start = i == 0 || chars[i - 1].is_whitespace();
+        if starts_with(i, syntax.line_comment) && (!syntax.word_start || word_start) {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            *commented.last_mut().expect("at least one line") = true;
+            continue;
+        }
+
+        if let Some((open, close)) = syntax.block_comment
+            && starts_with(i, open)
+        {
+            let mut depth = 0;
+            while i < chars.len() {
+                if starts_with(i, open) && (depth == 0 || syntax.nested_blocks) {
+                    depth += 1;
+                    i += open.len();
+                } else if starts_with(i, close) {
+                    depth -= 1;
+                    i += close.len();
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+            *commented.last_mut().expect("at least one line") = true;
+            continue;
+        }
+
+        let end = string_end(&chars, i, syntax);
+        if end > i {
+            for &c in &chars[i..end] {
+                out.push(c);
+                if c == '\n' {
+                    commented.push(false);
+                }
+            }
+            i = end;
+            continue;
+        }
+
+        out.push(c);
+        if c == '\n' {
+            commented.push(false);
+        }
+        i += 1;
+    }
+
+    (out, commented)
+}
+
+/// If a string or char literal starts at `i`, returns the index just past it; otherwise `i`.
+/// Unterminated single-line strings end at the line break, so one stray quote cannot swallow
+/// the rest of the file.
+fn string_end(chars: &[char], i: usize, syntax: &Syntax) -> usize {
+    let c = chars[i];
+    let prev_is_ident = i > 0 && (chars[i - 1].is_alphanumeric() || chars[i - 1] == '_');
+
+    if syntax.rust {
+        // Raw strings: r"...", r#"..."#, br"...".
+        if c == 'r' && !(prev_is_ident && chars[i - 1] != 'b') {
+            let hashes = chars[i + 1..].iter().take_while(|&&c| c == '#').count();
+            if chars.get(i + 1 + hashes) == Some(&'"') {
+                let mut j = i + 2 + hashes;
+                while j < chars.len() {
+                    if chars[j] == '"'
+                        && chars[j + 1..].iter().take_while(|&&c| c == '#').count() >= hashes
+                    {
+                        return j + 1 + hashes;
+                    }
+                    j += 1;
+                }
+                return chars.len();
+            }
+        }
+        // Char literals ('a', '\n', '\u{..}'); anything else is a lifetime or label.
+        if c == '\'' {
+            let is_char = chars.get(i + 1) == Some(&'\\') || chars.get(i + 2) == Some(&'\'');
+            if !is_char {
+                return i;
+            }
+            let mut j = i + 1;
+            while j < chars.len() && chars[j] != '\'' && chars[j] != '\n' {
+                j += if chars[j] == '\\' { 2 } else { 1 };
+            }
+            return (j + 1).min(chars.len());
+        }
+    }
+
+    if syntax.triple_quotes
+        && (c == '"' || c == '\'')
+        && chars.get(i + 1) == Some(&c)
+        && chars.get(i + 2) == Some(&c)
+    {
+        let mut j = i + 3;
+        while j < chars.len() {
+            if chars[j] == '\\' {
+                j += 2;
+                continue;
+            }
+            if chars[j] == c && chars.get(j + 1) == Some(&c) && chars.get(j + 2) == Some(&c) {
+                return j + 3;
+            }
+            j += 1;
+        }
+        return chars.len();
+    }
+
+    if syntax.raw_quotes.contains(&c) {
+        return match chars[i + 1..].iter().position(|&ch| ch == c) {
+            Some(offset) => i + offset + 2,
+            None => chars.len(),
+        };
+    }
+
+    let multi_line = syntax.multi_line_quotes.contains(&c);
+    if multi_line || syntax.quotes.contains(&c) {
+        let mut j = i + 1;
+        while j < chars.len() {
+            match chars[j] {
+                '\\' => j += 2,
+                '\n' if !multi_line => return j,
+                ch if ch == c => return j + 1,
+                _ => j += 1,
+            }
+        }
+        return chars.len();
+    }
+
+    i
+}
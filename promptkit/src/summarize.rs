@@ -0,0 +1,34 @@
+use anyhow::{Context, Result};
+
+use crate::llm;
+
+const MAX_SUMMARY_TOKENS: u32 = 1024;
+const RETRIES: u32 = 3;
+
+/// Asks `model` for a short summary of a file, to embed in place of its contents.
+pub fn summarize(
+    model: &str,
+    path: &str,
+    contents: &str,
+    language: Option<&str>,
+) -> Result<String> {
+    let fence = crate::fence_for(contents);
+    let prompt = format!(
+        "Summarize the file `{path}` below for a developer who will work on the code base \
+         without seeing the file. In at most 200 words, describe its purpose, its main types \
+         and functions with their signatures, and anything non-obvious about how it is used. \
+         Reply with the summary only.\n\n{fence}{}\n{contents}\n{fence}\n",
+        language.unwrap_or("")
+    );
+    let request = llm::ChatRequest {
+        provider: llm::Provider::for_model(model),
+        model,
+        base_url: None,
+        prompt: &prompt,
+        max_output_tokens: MAX_SUMMARY_TOKENS,
+        retries: RETRIES,
+    };
+    let mut reply = Vec::new();
+    llm::send(&request, &mut reply).with_context(|| format!("failed to summarize {path}"))?;
+    Ok(String::from_utf8_lossy(&reply).trim().to_string())
+}
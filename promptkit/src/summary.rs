@@ -0,0 +1,315 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::{FileDump, stats, with_thousands};
+
+/// Rows shown in the language and directory lists; the rest are counted on one line.
+const MAX_LANGUAGES: usize = 10;
+const MAX_DIRS: usize = 5;
+/// Path components grouped into a directory, as `stats --depth 2`.
+const DIR_DEPTH: usize = 2;
+/// Manifest paths listed for a build tool.
+const MAX_MANIFESTS: usize = 3;
+
+/// Build tools, by the file name that marks them.
+const TOOLS: &[(&str, &str)] = &[
+    ("Cargo.toml", "Cargo"),
+    ("package.json", "npm"),
+    ("yarn.lock", "Yarn"),
+    ("pnpm-lock.yaml", "pnpm"),
+    ("bun.lock", "Bun"),
+    ("pyproject.toml", "pyproject"),
+    ("setup.py", "setuptools"),
+    ("requirements.txt", "pip"),
+    ("Pipfile", "Pipenv"),
+    ("poetry.lock", "Poetry"),
+    ("uv.lock", "uv"),
+    ("go.mod", "Go modules"),
+    ("pom.xml", "Maven"),
+    ("build.gradle", "Gradle"),
+    ("build.gradle.kts", "Gradle"),
+    ("Gemfile", "Bundler"),
+    ("composer.json", "Composer"),
+    ("mix.exs", "Mix"),
+    ("Package.swift", "Swift Package Manager"),
+    ("CMakeLists.txt", "CMake"),
+    ("Makefile", "Make"),
+    ("Dockerfile", "Docker"),
+    ("docker-compose.yml", "Docker Compose"),
+    ("compose.yaml", "Docker Compose"),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Manifest {
+    Cargo,
+    Npm,
+    Python,
+    Go,
+    Jvm,
+    Ruby,
+    Php,
+}
+
+/// Frameworks and major libraries, by the dependency that marks them. A trailing `*`
+/// matches any dependency starting with the rest.
+const FRAMEWORKS: &[(Manifest, &str, &str)] = &[
+    (Manifest::Cargo, "tokio", "Tokio"),
+    (Manifest::Cargo, "axum", "Axum"),
+    (Manifest::Cargo, "actix-web", "Actix Web"),
+    (Manifest::Cargo, "rocket", "Rocket"),
+    (Manifest::Cargo, "bevy", "Bevy"),
+    (Manifest::Cargo, "tauri", "Tauri"),
+    (Manifest::Cargo, "leptos", "Leptos"),
+    (Manifest::Cargo, "clap", "clap"),
+    (Manifest::Npm, "react", "React"),
+    (Manifest::Npm, "next", "Next.js"),
+    (Manifest::Npm, "vue", "Vue"),
+    (Manifest::Npm, "nuxt", "Nuxt"),
+    (Manifest::Npm, "svelte", "Svelte"),
+    (Manifest::Npm, "@angular/core", "Angular"),
+    (Manifest::Npm, "express", "Express"),
+    (Manifest::Npm, "fastify", "Fastify"),
+    (Manifest::Npm, "@nestjs/core", "NestJS"),
+    (Manifest::Npm, "electron", "Electron"),
+    (Manifest::Npm, "typescript", "TypeScript"),
+    (Manifest::Npm, "vite", "Vite"),
+    (Manifest::Npm, "jest", "Jest"),
+    (Manifest::Npm, "vitest", "Vitest"),
+    (Manifest::Python, "django", "Django"),
+    (Manifest::Python, "flask", "Flask"),
+    (Manifest::Python, "fastapi", "FastAPI"),
+    (Manifest::Python, "torch", "PyTorch"),
+    (Manifest::Python, "pandas", "pandas"),
+    (Manifest::Python, "pytest", "pytest"),
+    (Manifest::Go, "github.com/gin-gonic/gin", "Gin"),
+    (Manifest::Go, "github.com/labstack/echo/v4", "Echo"),
+    (Manifest::Go, "github.com/gofiber/fiber/v2", "Fiber"),
+    (Manifest::Go, "github.com/spf13/cobra", "Cobra"),
+    (Manifest::Jvm, "spring-boot*", "Spring Boot"),
+    (Manifest::Jvm, "org.springframework.boot", "Spring Boot"),
+    (Manifest::Jvm, "junit*", "JUnit"),
+    (Manifest::Ruby, "rails", "Rails"),
+    (Manifest::Ruby, "rspec", "RSpec"),
+    (Manifest::Php, "laravel/framework", "Laravel"),
+    (Manifest::Php, "symfony/framework-bundle", "Symfony"),
+];
+
+#[derive(Default)]
+struct Totals {
+    files: usize,
+    lines: usize,
+    tokens: usize,
+}
+
+impl Totals {
+    fn add(&mut self, file: &FileDump, tokens: usize) {
+        self.files += 1;
+        self.lines += file.contents.lines().count();
+        self.tokens += tokens;
+    }
+}
+
+/// Renders the `## Repository Summary` section: languages by lines and tokens, the largest
+/// directories, and the build tools and frameworks detected from manifests among `files`.
+/// `tokens` holds the token count of each file.
+pub fn describe(files: &[FileDump], tokens: &[usize]) -> Result<String> {
+    let mut total = Totals::default();
+    let mut languages: BTreeMap<&str, Totals> = BTreeMap::new();
+    let mut dirs: BTreeMap<String, Totals> = BTreeMap::new();
+    for (file, &tokens) in files.iter().zip(tokens) {
+        total.add(file, tokens);
+        languages
+            .entry(file.language.as_deref().unwrap_or("other"))
+            .or_default()
+            .add(file, tokens);
+        dirs.entry(stats::dir_key(Path::new(&file.relative_path), DIR_DEPTH))
+            .or_default()
+            .add(file, tokens);
+    }
+
+    let mut section = String::new();
+    writeln!(section, "## Repository Summary")?;
+    writeln!(
+        section,
+        "Files: {}, lines: {}, tokens: {}",
+        with_thousands(total.files),
+        with_thousands(total.lines),
+        with_thousands(total.tokens)
+    )?;
+    writeln!(section)?;
+
+    writeln!(section, "### Languages")?;
+    write_rows(&mut section, languages, total.tokens, MAX_LANGUAGES)?;
+    writeln!(section)?;
+
+    writeln!(section, "### Largest Directories")?;
+    write_rows(&mut section, dirs, total.tokens, MAX_DIRS)?;
+    writeln!(section)?;
+
+    let tools = build_tools(files);
+    if !tools.is_empty() {
+        writeln!(section, "### Build Tools")?;
+        for (tool, paths) in &tools {
+            let mut line = paths[..paths.len().min(MAX_MANIFESTS)].join(", ");
+            if paths.len() > MAX_MANIFESTS {
+                write!(line, " and {} more", paths.len() - MAX_MANIFESTS)?;
+            }
+            writeln!(section, "- {tool}: {line}")?;
+        }
+        writeln!(section)?;
+    }
+
+    let frameworks = frameworks(files);
+    if !frameworks.is_empty() {
+        writeln!(section, "### Frameworks")?;
+        for (framework, path) in &frameworks {
+            writeln!(section, "- {framework} ({path})")?;
+        }
+        writeln!(section)?;
+    }
+
+    Ok(section)
+}
+
+/// One line per row, most tokens first, with the rows past `max` counted on the last.
+fn write_rows<K: AsRef<str>>(
+    out: &mut String,
+    rows: BTreeMap<K, Totals>,
+    total_tokens: usize,
+    max: usize,
+) -> Result<()> {
+    let mut rows: Vec<(K, Totals)> = rows.into_iter().collect();
+    rows.sort_by_key(|(_, totals)| Reverse(totals.tokens));
+    for (name, totals) in rows.iter().take(max) {
+        writeln!(
+            out,
+            "- {}: {} files, {} lines, {} tokens ({}%)",
+            name.as_ref(),
+            with_thousands(totals.files),
+            with_thousands(totals.lines),
+            with_thousands(totals.tokens),
+            (totals.tokens * 100)
+                .checked_div(total_tokens)
+                .unwrap_or_default()
+        )?;
+    }
+    if rows.len() > max {
+        writeln!(out, "- ... {} more", rows.len() - max)?;
+    }
+    Ok(())
+}
+
+/// Each detected build tool with the paths of the files that mark it, in [`TOOLS`] order.
+fn build_tools(files: &[FileDump]) -> Vec<(&'static str, Vec<&str>)> {
+    let mut tools: Vec<(&'static str, Vec<&str>)> = Vec::new();
+    for &(marker, tool) in TOOLS {
+        let paths = files
+            .iter()
+            .filter(|file| file_name(&file.relative_path) == marker)
+            .map(|file| file.relative_path.as_str());
+        match tools.iter_mut().find(|(known, _)| *known == tool) {
+            Some((_, known_paths)) => known_paths.extend(paths),
+            None => tools.push((tool, paths.collect())),
+        }
+    }
+    tools.retain(|(_, paths)| !paths.is_empty());
+    tools
+}
+
+/// Each detected framework with the first manifest that depends on it, in [`FRAMEWORKS`] order.
+fn frameworks(files: &[FileDump]) -> Vec<(&'static str, &str)> {
+    let manifests: Vec<(Manifest, &str, BTreeSet<String>)> = files
+        .iter()
+        .filter(|file| file.condensed.is_none())
+        .filter_map(|file| {
+            let manifest = manifest(file_name(&file.relative_path))?;
+            let dependencies = dependencies(manifest, &file.contents);
+            Some((manifest, file.relative_path.as_str(), dependencies))
+        })
+        .collect();
+
+    let mut found: Vec<(&'static str, &str)> = Vec::new();
+    for &(kind, needle, framework) in FRAMEWORKS {
+        if found.iter().any(|(known, _)| *known == framework) {
+            continue;
+        }
+        let hit = |dependency: &String| match needle.strip_suffix('*') {
+            Some(prefix) => dependency.starts_with(prefix),
+            None => dependency == needle,
+        };
+        if let Some((_, path, _)) = manifests
+            .iter()
+            .find(|(manifest, _, dependencies)| *manifest == kind && dependencies.iter().any(hit))
+        {
+            found.push((framework, path));
+        }
+    }
+    found
+}
+
+fn file_name(relative_path: &str) -> &str {
+    relative_path
+        .rsplit(['/', std::path::MAIN_SEPARATOR])
+        .next()
+        .unwrap_or(relative_path)
+}
+
+fn manifest(name: &str) -> Option<Manifest> {
+    Some(match name {
+        "Cargo.toml" => Manifest::Cargo,
+        "package.json" => Manifest::Npm,
+        "pyproject.toml" | "setup.py" | "setup.cfg" | "Pipfile" => Manifest::Python,
+        _ if name.starts_with("requirements") && name.ends_with(".txt") => Manifest::Python,
+        "go.mod" => Manifest::Go,
+        "pom.xml" | "build.gradle" | "build.gradle.kts" => Manifest::Jvm,
+        "Gemfile" => Manifest::Ruby,
+        "composer.json" => Manifest::Php,
+        _ => return None,
+    })
+}
+
+/// Names of the dependencies declared in a manifest. Cargo and JSON manifests are parsed;
+/// for the others, every word is a candidate, which is enough to spot well-known names.
+fn dependencies(manifest: Manifest, contents: &str) -> BTreeSet<String> {
+    match manifest {
+        Manifest::Cargo => {
+            let Ok(table) = contents.parse::<toml::Table>() else {
+                return BTreeSet::new();
+            };
+            let workspace = table.get("workspace").and_then(|value| value.as_table());
+            ["dependencies", "dev-dependencies", "build-dependencies"]
+                .iter()
+                .filter_map(|key| table.get(*key))
+                .chain(workspace.and_then(|workspace| workspace.get("dependencies")))
+                .filter_map(|value| value.as_table())
+                .flat_map(|deps| deps.keys().cloned())
+                .collect()
+        }
+        Manifest::Npm | Manifest::Php => {
+            let Ok(json) = serde_json::from_str::<serde_json::Value>(contents) else {
+                return BTreeSet::new();
+            };
+            [
+                "dependencies",
+                "devDependencies",
+                "peerDependencies",
+                "require",
+                "require-dev",
+            ]
+            .iter()
+            .filter_map(|key| json.get(*key)?.as_object())
+            .flat_map(|deps| deps.keys().cloned())
+            .collect()
+        }
+        Manifest::Python | Manifest::Go | Manifest::Jvm | Manifest::Ruby => contents
+            .to_ascii_lowercase()
+            .split(|c: char| !(c.is_ascii_alphanumeric() || "-_./@".contains(c)))
+            .filter(|word| !word.is_empty())
+            .map(str::to_string)
+            .collect(),
+    }
+}
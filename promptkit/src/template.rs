@@ -0,0 +1,54 @@
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+use anyhow::{Result, bail};
+use regex::Regex;
+
+/// `{{name}}`, `{{files}}` or `{{file:PATH}}`, with optional spaces inside the braces.
+static PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_-]*)(?:\s*:\s*([^{}]*?))?\s*\}\}")
+        .expect("invalid placeholder pattern")
+});
+
+/// File contents a template asks for, rendered by the caller.
+pub enum Embed<'a> {
+    /// `{{files}}`: every file passed with `--file`.
+    Files,
+    /// `{{file:PATH}}`: one file, embedded where it is referenced.
+    File(&'a str),
+}
+
+/// Expands the placeholders of `template`. Variables take precedence over `{{files}}`, and
+/// unknown variables are an error rather than left in the output.
+pub fn expand(
+    template: &str,
+    vars: &BTreeMap<String, String>,
+    mut embed: impl FnMut(Embed) -> Result<String>,
+) -> Result<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut last = 0;
+    for caps in PLACEHOLDER.captures_iter(template) {
+        let whole = caps.get(0).expect("whole match");
+        out.push_str(&template[last..whole.start()]);
+        last = whole.end();
+
+        let name = &caps[1];
+        match (name, caps.get(2)) {
+            ("file", Some(path)) => out.push_str(&embed(Embed::File(path.as_str()))?),
+            (_, Some(_)) => bail!("unknown placeholder {}", whole.as_str()),
+            _ if vars.contains_key(name) => out.push_str(&vars[name]),
+            ("files", None) => out.push_str(&embed(Embed::Files)?),
+            _ => bail!("no value for {{{{{name}}}}}; pass it with --var {name}=VALUE"),
+        }
+    }
+    out.push_str(&template[last..]);
+    Ok(out)
+}
+
+/// Parses a `--var NAME=VALUE` argument.
+pub fn parse_var(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("expected NAME=VALUE, got {arg:?}")),
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::compare::Change;
+use crate::{FileDump, OmittedFile, Symlink, with_thousands};
+
+/// What a file tree entry stands for.
+pub enum Leaf {
+    File,
+    /// A file that differs between the two versions of a `--compare`.
+    Changed(Change),
+    Symlink(String),
+    /// A file left out of the dump, with its size in bytes.
+    Omitted(u64),
+}
+
+/// Files, tokens and bytes of a file or subtree.
+#[derive(Clone, Copy, Default)]
+pub struct Size {
+    pub files: usize,
+    pub tokens: usize,
+    pub bytes: usize,
+}
+
+impl Size {
+    fn add(&mut self, other: Size) {
+        self.files += other.files;
+        self.tokens += other.tokens;
+        self.bytes += other.bytes;
+    }
+}
+
+/// How [`Tree::render`] lays out entries.
+#[derive(Clone, Copy, Default)]
+pub struct RenderOptions {
+    /// Directories this deep are collapsed into one line with their file count.
+    pub max_depth: Option<usize>,
+    /// Annotate entries with their token and byte counts.
+    pub sizes: bool,
+    /// Annotate included entries with their file count, approximate tokens and a bar for
+    /// their share of this many tokens, instead of `sizes`.
+    pub budget: Option<usize>,
+}
+
+/// Token counts of a dump's files, for `--tree-tokens`.
+pub struct TreeTokens {
+    pub tokens: HashMap<String, usize>,
+    /// What each entry's share is of: `--max-tokens`, or the tokens of all included files.
+    pub budget: usize,
+}
+
+/// Cells in a `--tree-tokens` bar.
+const BAR_WIDTH: usize = 10;
+
+#[derive(Default)]
+pub struct Tree {
+    root: Node,
+}
+
+#[derive(Default)]
+struct Node {
+    children: BTreeMap<String, Node>,
+    leaf: Option<Leaf>,
+    /// Summed over the subtree.
+    size: Size,
+}
+
+/// Files, bytes and extensions of a subtree whose files were all left out.
+#[derive(Default)]
+struct OmittedTotals {
+    files: usize,
+    bytes: u64,
+    extensions: BTreeSet<String>,
+}
+
+impl Tree {
+    pub fn insert(&mut self, relative_path: &str, leaf: Leaf, size: Size) {
+        let components: Vec<String> = Path::new(relative_path)
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let mut node = &mut self.root;
+        node.size.add(size);
+        for component in components {
+            node = node.children.entry(component).or_default();
+            node.size.add(size);
+        }
+        node.leaf = Some(leaf);
+    }
+
+    pub fn render(&self, options: RenderOptions) -> String {
+        let mut lines = Vec::new();
+        let mut root = ".".to_string();
+        if let Some(budget) = options.budget {
+            root.push_str(&budget_annotation(self.root.size, true, budget));
+        } else if options.sizes {
+            root.push_str(&annotation(self.root.size, true));
+        }
+        lines.push(root);
+        render_node(&self.root, "", 1, options, &mut lines);
+        lines.join("\n")
+    }
+}
+
+impl Node {
+    /// Totals of the subtree if it only contains omitted files.
+    fn omitted_totals(&self, name: &str) -> Option<OmittedTotals> {
+        match &self.leaf {
+            Some(Leaf::Omitted(bytes)) => {
+                let mut totals = OmittedTotals {
+                    files: 1,
+                    bytes: *bytes,
+                    ..Default::default()
+                };
+                if let Some(ext) = Path::new(name).extension() {
+                    totals
+                        .extensions
+                        .insert(format!(".{}", ext.to_string_lossy()));
+                }
+                return Some(totals);
+            }
+            Some(_) => return None,
+            None => {}
+        }
+        let mut totals = OmittedTotals::default();
+        for (name, child) in &self.children {
+            let child = child.omitted_totals(name)?;
+            totals.files += child.files;
+            totals.bytes += child.bytes;
+            totals.extensions.extend(child.extensions);
+        }
+        Some(totals)
+    }
+}
+
+/// Renders the file tree of a dump: its files, plus unfollowed symlinks, omitted files and,
+/// with `--compare`, files that did not change.
+///
+/// Directories whose files were all omitted are collapsed into a one-line summary. With
+/// `tokens`, included files and directories are annotated with their share of the budget.
+pub fn file_tree(
+    files: &[FileDump],
+    symlinks: &[Symlink],
+    omitted: &[OmittedFile],
+    unchanged: &[String],
+    tokens: Option<&TreeTokens>,
+) -> String {
+    let mut tree = Tree::default();
+    for file in files {
+        let leaf = match file.change {
+            Some(change) => Leaf::Changed(change),
+            None => Leaf::File,
+        };
+        let size = match tokens {
+            Some(tokens) => Size {
+                files: 1,
+                tokens: tokens.tokens.get(&file.relative_path).copied().unwrap_or(0),
+                bytes: file.contents.len(),
+            },
+            None => Size::default(),
+        };
+        tree.insert(&file.relative_path, leaf, size);
+    }
+    for path in unchanged {
+        tree.insert(path, Leaf::File, Size::default());
+    }
+    for link in symlinks {
+        tree.insert(
+            &link.relative_path,
+            Leaf::Symlink(link.target.clone()),
+            Size::default(),
+        );
+    }
+    for file in omitted {
+        tree.insert(
+            &file.relative_path,
+            Leaf::Omitted(file.bytes),
+            Size::default(),
+        );
+    }
+    tree.render(RenderOptions {
+        budget: tokens.map(|tokens| tokens.budget),
+        ..RenderOptions::default()
+    })
+}
+
+fn render_node(
+    node: &Node,
+    prefix: &str,
+    depth: usize,
+    options: RenderOptions,
+    lines: &mut Vec<String>,
+) {
+    let total = node.children.len();
+    for (idx, (name, child)) in node.children.iter().enumerate() {
+        let is_last = idx + 1 == total;
+        let connector = if is_last { "`-- " } else { "|-- " };
+        let mut line = String::new();
+        line.push_str(prefix);
+        line.push_str(connector);
+        line.push_str(name);
+
+        let is_dir = child.leaf.is_none();
+        if is_dir {
+            line.push('/');
+        }
+        match &child.leaf {
+            Some(Leaf::Symlink(target)) => {
+                line.push_str(" -> ");
+                line.push_str(target);
+            }
+            Some(Leaf::Omitted(bytes)) => {
+                line.push_str(&format!(
+                    " [not included, {} bytes]",
+                    with_thousands(*bytes as usize)
+                ));
+            }
+            Some(Leaf::Changed(change)) => {
+                line.push_str(&format!(" [{}]", change.label()));
+            }
+            Some(Leaf::File) | None => {}
+        }
+        if matches!(child.leaf, Some(Leaf::File | Leaf::Changed(_))) {
+            match options.budget {
+                Some(budget) if child.size.files > 0 => {
+                    line.push_str(&budget_annotation(child.size, false, budget));
+                }
+                Some(_) => {}
+                None if options.sizes => line.push_str(&annotation(child.size, false)),
+                None => {}
+            }
+        }
+
+        let summary = is_dir.then(|| child.omitted_totals(name)).flatten();
+        if let Some(totals) = &summary {
+            let mut extensions: Vec<&str> = totals
+                .extensions
+                .iter()
+                .map(String::as_str)
+                .take(5)
+                .collect();
+            if totals.extensions.len() > extensions.len() {
+                extensions.push("...");
+            }
+            line.push_str(&format!(" [not included: {} files", totals.files));
+            if !extensions.is_empty() {
+                line.push_str(&format!(" ({})", extensions.join(", ")));
+            }
+            line.push_str(&format!(
+                ", {} bytes]",
+                with_thousands(totals.bytes as usize)
+            ));
+        }
+
+        let collapsed = is_dir
+            && summary.is_none()
+            && options
+                .max_depth
+                .is_some_and(|max_depth| depth >= max_depth);
+        if is_dir && summary.is_none() {
+            match options.budget {
+                Some(budget) if child.size.files > 0 => {
+                    line.push_str(&budget_annotation(child.size, true, budget));
+                }
+                Some(_) => {}
+                None if options.sizes => line.push_str(&annotation(child.size, true)),
+                None if collapsed => {
+                    line.push_str(&format!(" ({} files)", with_thousands(child.size.files)));
+                }
+                None => {}
+            }
+        }
+        lines.push(line);
+
+        if !child.children.is_empty() && summary.is_none() && !collapsed {
+            let mut new_prefix = String::from(prefix);
+            new_prefix.push_str(if is_last { "    " } else { "|   " });
+            render_node(child, &new_prefix, depth + 1, options, lines);
+        }
+    }
+}
+
+/// ` (1,234 tokens, 5,678 bytes)`, with the file count first if `with_files`.
+fn annotation(size: Size, with_files: bool) -> String {
+    let mut parts = Vec::new();
+    if with_files {
+        parts.push(format!("{} files", with_thousands(size.files)));
+    }
+    parts.push(format!("{} tokens", with_thousands(size.tokens)));
+    parts.push(format!("{} bytes", with_thousands(size.bytes)));
+    format!(" ({})", parts.join(", "))
+}
+
+/// ` (42 files, ~18.2k tokens) [###.......] 31%`, with the file count only if `with_files`.
+fn budget_annotation(size: Size, with_files: bool, budget: usize) -> String {
+    let share = size.tokens as f64 / budget.max(1) as f64;
+    let filled = ((share * BAR_WIDTH as f64).round() as usize).min(BAR_WIDTH);
+    let bar = "#".repeat(filled) + &".".repeat(BAR_WIDTH - filled);
+    let tokens = approximate(size.tokens);
+    let counts = if with_files {
+        format!("{} files, ~{tokens} tokens", with_thousands(size.files))
+    } else {
+        format!("~{tokens} tokens")
+    };
+    format!(" ({counts}) [{bar}] {:.0}%", share * 100.0)
+}
+
+/// `950`, `18.2k` or `1.3M`.
+fn approximate(n: usize) -> String {
+    match n {
+        0..1_000 => n.to_string(),
+        1_000..1_000_000 => format!("{:.1}k", n as f64 / 1e3),
+        _ => format!("{:.1}M", n as f64 / 1e6),
+    }
+}
@@ -0,0 +1,67 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use notify::{Event, RecursiveMode, Watcher};
+
+use crate::DEFAULT_IGNORED_DIRS;
+
+/// Calls `rebuild` with the changed paths whenever files under `root` change.
+///
+/// Events are debounced: after the first change, further changes are collected until none
+/// arrive for `debounce`. Paths in `ignored` (e.g. the dump's own output) and the default
+/// ignored directories never trigger a rebuild.
+pub fn watch(
+    root: &Path,
+    ignored: &[PathBuf],
+    debounce: Duration,
+    mut rebuild: impl FnMut(&[PathBuf]) -> Result<()>,
+) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).context("failed to create file watcher")?;
+    watcher
+        .watch(root, RecursiveMode::Recursive)
+        .with_context(|| format!("failed to watch {}", root.display()))?;
+    eprintln!("Watching {} for changes...", root.display());
+
+    let relevant = |event: notify::Result<Event>| -> Result<Vec<PathBuf>> {
+        let event = event.context("file watcher error")?;
+        // Rebuilding reads every file, which would otherwise retrigger the watcher.
+        if event.kind.is_access() {
+            return Ok(Vec::new());
+        }
+        Ok(event
+            .paths
+            .into_iter()
+            .filter(|path| {
+                !ignored.iter().any(|ignored| path.starts_with(ignored))
+                    && !path
+                        .strip_prefix(root)
+                        .unwrap_or(path)
+                        .components()
+                        .any(|component| {
+                            component
+                                .as_os_str()
+                                .to_str()
+                                .is_some_and(|name| DEFAULT_IGNORED_DIRS.contains(&name))
+                        })
+            })
+            .collect())
+    };
+
+    while let Ok(event) = rx.recv() {
+        let mut changed = relevant(event)?;
+        if changed.is_empty() {
+            continue;
+        }
+        while let Ok(event) = rx.recv_timeout(debounce) {
+            changed.extend(relevant(event)?);
+        }
+        changed.sort();
+        changed.dedup();
+        rebuild(&changed)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use anyhow::{Result, bail};
+use serde_json::Value as Json;
+use toml::Value as Toml;
+
+use crate::FileDump;
+
+/// Files at the root that describe the workspace as a whole, kept with any selection.
+const WORKSPACE_FILES: [&str; 4] = [
+    "Cargo.toml",
+    "package.json",
+    "pnpm-workspace.yaml",
+    "go.work",
+];
+
+/// A package of a Cargo, npm/pnpm/yarn or Go workspace, found by its manifest.
+struct Package {
+    name: String,
+    /// Directory relative to the root, `""` for the root itself.
+    dir: String,
+    /// Names of the packages it depends on; only in-repo ones matter.
+    dependencies: Vec<String>,
+}
+
+/// Paths of the files belonging to the `members` (by package name or directory) and to the
+/// in-repo packages they depend on, directly or not.
+///
+/// Packages are found from the `Cargo.toml`, `package.json` and `go.mod` files among `files`;
+/// each file belongs to the package with the closest enclosing directory.
+pub fn select(files: &[FileDump], members: &[String]) -> Result<BTreeSet<String>> {
+    let packages: Vec<Package> = files.iter().filter_map(package).collect();
+    if packages.is_empty() {
+        bail!("no Cargo.toml, package.json or go.mod packages found for --workspace-member");
+    }
+    let by_name: BTreeMap<&str, &Package> = packages
+        .iter()
+        .map(|package| (package.name.as_str(), package))
+        .collect();
+
+    let mut queue = Vec::new();
+    for member in members {
+        let member = member.trim_end_matches('/');
+        match packages
+            .iter()
+            .find(|package| package.name == member || package.dir == member)
+        {
+            Some(package) => queue.push(package),
+            None => bail!(
+                "unknown workspace member {member}; available: {}",
+                by_name.keys().copied().collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+    let mut selected: BTreeSet<&str> = BTreeSet::new();
+    while let Some(package) = queue.pop() {
+        if !selected.insert(&package.dir) {
+            continue;
+        }
+        queue.extend(
+            package
+                .dependencies
+                .iter()
+                .filter_map(|name| by_name.get(name.as_str()).copied()),
+        );
+    }
+
+    let dirs: BTreeSet<&str> = packages
+        .iter()
+        .map(|package| package.dir.as_str())
+        .collect();
+    Ok(files
+        .iter()
+        .map(|file| file.relative_path.as_str())
+        .filter(|path| WORKSPACE_FILES.contains(path) || selected.contains(owner(path, &dirs)))
+        .map(str::to_string)
+        .collect())
+}
+
+/// The package directory closest to `path`.
+fn owner<'a>(path: &str, dirs: &BTreeSet<&'a str>) -> &'a str {
+    Path::new(path)
+        .ancestors()
+        .skip(1)
+        .find_map(|ancestor| dirs.get(ancestor.to_str()?).copied())
+        .unwrap_or("")
+}
+
+/// The package described by `file`, if it is a package manifest.
+fn package(file: &FileDump) -> Option<Package> {
+    let path = Path::new(&file.relative_path);
+    let dir = path.parent()?.to_str()?.to_string();
+    let (name, dependencies) = match path.file_name()?.to_str()? {
+        "Cargo.toml" => cargo_package(&file.contents)?,
+        "package.json" => npm_package(&file.contents)?,
+        "go.mod" => go_module(&file.contents)?,
+        _ => return None,
+    };
+    Some(Package {
+        name,
+        dir,
+        dependencies,
+    })
+}
+
+fn cargo_package(manifest: &str) -> Option<(String, Vec<String>)> {
+    let manifest: Toml = toml::from_str(manifest).ok()?;
+    let name = manifest.get("package")?.get("name")?.as_str()?.to_string();
+
+    let mut tables = vec![&manifest];
+    if let Some(targets) = manifest.get("target").and_then(Toml::as_table) {
+        tables.extend(targets.values());
+    }
+    let mut dependencies = Vec::new();
+    for table in tables {
+        for section in ["dependencies", "dev-dependencies", "build-dependencies"] {
+            let Some(deps) = table.get(section).and_then(Toml::as_table) else {
+                continue;
+            };
+            for (key, spec) in deps {
+                // `alias = { package = "real-name", ... }`
+                match spec.get("package").and_then(Toml::as_str) {
+                    Some(real) => dependencies.push(real.to_string()),
+                    None => dependencies.push(key.clone()),
+                }
+            }
+        }
+    }
+    Some((name, dependencies))
+}
+
+fn npm_package(manifest: &str) -> Option<(String, Vec<String>)> {
+    let manifest: Json = serde_json::from_str(manifest).ok()?;
+    let name = manifest.get("name")?.as_str()?.to_string();
+    let dependencies = [
+        "dependencies",
+        "devDependencies",
+        "peerDependencies",
+        "optionalDependencies",
+    ]
+    .iter()
+    .filter_map(|section| manifest.get(section)?.as_object())
+    .flat_map(|deps| deps.keys().cloned())
+    .collect();
+    Some((name, dependencies))
+}
+
+/// The module path and `require`d modules of a `go.mod`.
+fn go_module(manifest: &str) -> Option<(String, Vec<String>)> {
+    let mut name = None;
+    let mut dependencies = Vec::new();
+    let mut in_require = false;
+    for line in manifest.lines() {
+        let line = line.split("//").next().unwrap_or("").trim();
+        if in_require {
+            if line == ")" {
+                in_require = false;
+            } else if let Some(module) = line.split_whitespace().next() {
+                dependencies.push(module.to_string());
+            }
+        } else if let Some(module) = line.strip_prefix("module ") {
+            name = Some(module.trim().trim_matches('"').to_string());
+        } else if let Some(rest) = line.strip_prefix("require") {
+            match rest.trim() {
+                "(" => in_require = true,
+                rest => dependencies.extend(rest.split_whitespace().next().map(str::to_string)),
+            }
+        }
+    }
+    Some((name?, dependencies))
+}